@@ -0,0 +1,74 @@
+//! Cost of drawing and hit-testing the decorations across window sizes and scales.
+//!
+//! Run with `cargo bench`, a name filter can be passed as in `cargo bench -- 4k`. Everything
+//! runs headless, so the numbers leave out shm uploads and compositor round trips.
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use sctk_adwaita::{draw_decorations, hit_test, render_decorations, RenderState};
+
+/// How long each case is measured for, after a warm up of the same length.
+const MEASURE: Duration = Duration::from_millis(500);
+
+const SIZES: [(&str, (u32, u32)); 3] = [
+    ("small", (320, 240)),
+    ("hd", (1280, 720)),
+    ("4k", (3840, 2160)),
+];
+
+fn bench(filter: Option<&str>, name: &str, mut f: impl FnMut()) {
+    if filter.is_some_and(|filter| !name.contains(filter)) {
+        return;
+    }
+
+    let start = Instant::now();
+    while start.elapsed() < MEASURE {
+        f();
+    }
+
+    let mut iterations = 0u32;
+    let start = Instant::now();
+    while start.elapsed() < MEASURE {
+        f();
+        iterations += 1;
+    }
+    println!("{name:<32} {:>12.2?}", start.elapsed() / iterations);
+}
+
+fn main() {
+    // `cargo bench` passes `--bench` along with the filter.
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+    let filter = filter.as_deref();
+
+    for (size_name, size) in SIZES {
+        for scale in 1..=2 {
+            let state = RenderState {
+                title: Some("Benchmark".into()),
+                ..RenderState::new(size, scale)
+            };
+            // A canvas of the right size, drawn over again and again.
+            let mut canvas = render_decorations(&state).unwrap();
+
+            bench(filter, &format!("decorations/{size_name}@{scale}x"), || {
+                draw_decorations(black_box(&state), &mut canvas.as_mut(), (0, 0)).unwrap();
+            });
+
+            // Maximized windows only draw the header.
+            let maximized = RenderState {
+                maximized: true,
+                ..state.clone()
+            };
+            bench(filter, &format!("header/{size_name}@{scale}x"), || {
+                draw_decorations(black_box(&maximized), &mut canvas.as_mut(), (0, 0)).unwrap();
+            });
+
+            bench(filter, &format!("hit_test/{size_name}@{scale}x"), || {
+                for x in (0..size.0).step_by(16) {
+                    black_box(hit_test(&state, f64::from(x), 20.0));
+                }
+            });
+        }
+    }
+}
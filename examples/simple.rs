@@ -42,7 +42,7 @@ fn main() {
     window.set_title("/usr/lib/xorg/modules/input".to_string());
 
     // // uncomment to override automatic theme selection
-    // window.set_frame_config(sctk_adwaita::FrameConfig::light());
+    // window.set_frame_config(sctk_adwaita::AdwaitaConfig::light());
 
     let mut pool = env
         .create_auto_pool()
@@ -99,12 +99,8 @@ fn redraw(
         wl_shm::Format::Argb8888,
     )?;
 
-    for pixel in canvas.chunks_exact_mut(4) {
-        pixel[0] = 255;
-        pixel[1] = 255;
-        pixel[2] = 255;
-        pixel[3] = 255;
-    }
+    // Opaque white.
+    canvas.fill(255);
 
     surface.attach(Some(&new_buffer), 0, 0);
     if surface.as_ref().version() >= 4 {
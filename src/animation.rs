@@ -0,0 +1,185 @@
+//! Animations of the decorations, ticked by frame callbacks of the header surface.
+//!
+//! Animations only record when they started, every redraw looks up their progress. While any
+//! animation runs, each redraw asks for a frame callback which in turn requests the next redraw,
+//! so animations advance at the refresh rate of the output and stop on their own once done.
+use std::{
+    cell::Cell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use smithay_client_toolkit::reexports::client::{
+    protocol::{wl_callback, wl_surface::WlSurface},
+    DispatchData,
+};
+
+/// Things the frame animates.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AnimationKind {
+    /// Between the active and inactive colors.
+    Crossfade,
+    /// Decorations fading in when they are shown.
+    Appear,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Animation<K> {
+    kind: K,
+    start: Instant,
+    duration: Duration,
+}
+
+#[derive(Debug)]
+pub struct Animations<K> {
+    running: Vec<Animation<K>>,
+    /// Set while a requested frame callback didn't fire yet, shared with that callback.
+    callback_pending: Rc<Cell<bool>>,
+}
+
+impl<K> Default for Animations<K> {
+    fn default() -> Self {
+        Self {
+            running: Vec::new(),
+            callback_pending: Default::default(),
+        }
+    }
+}
+
+impl<K: Copy + PartialEq> Animations<K> {
+    /// Starts `kind`, restarting it if it is already running.
+    pub fn start(&mut self, kind: K, duration: Duration, now: Instant) {
+        self.running.retain(|a| a.kind != kind);
+        self.running.push(Animation {
+            kind,
+            start: now,
+            duration,
+        });
+    }
+
+    /// Progress of `kind` from `0.0` to `1.0`, `None` if it isn't running.
+    pub fn progress(&self, kind: K, now: Instant) -> Option<f32> {
+        let animation = self.running.iter().find(|a| a.kind == kind)?;
+        let elapsed = now.saturating_duration_since(animation.start);
+        Some((elapsed.as_secs_f32() / animation.duration.as_secs_f32()).min(1.0))
+    }
+
+    /// Drops animations that finished before `now`, their final state is drawn from then on.
+    pub fn tick(&mut self, now: Instant) {
+        self.running
+            .retain(|a| now.saturating_duration_since(a.start) < a.duration);
+    }
+
+    /// Stops all animations, their final state is drawn from now on.
+    ///
+    /// Also forgets a requested frame callback, which never fires if its surface got destroyed
+    /// or unmapped meanwhile.
+    pub fn clear(&mut self) {
+        self.running.clear();
+        self.callback_pending = Default::default();
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.running.is_empty()
+    }
+
+    /// Requests a frame callback on `surface` calling `refresh` if animations are running.
+    ///
+    /// Returns whether a callback got requested, `surface` has to be committed then.
+    pub fn schedule<F>(&self, surface: &WlSurface, mut refresh: F) -> bool
+    where
+        F: FnMut(DispatchData) + 'static,
+    {
+        let Some(callback_pending) = self.request_callback() else {
+            return false;
+        };
+        surface.frame().quick_assign(move |_, event, ddata| {
+            if let wl_callback::Event::Done { .. } = event {
+                callback_pending.set(false);
+                refresh(ddata);
+            }
+        });
+        true
+    }
+
+    /// Marks a frame callback as pending if one is needed, returns the flag it clears once done.
+    fn request_callback(&self) -> Option<Rc<Cell<bool>>> {
+        if !self.is_running() || self.callback_pending.get() {
+            return None;
+        }
+        self.callback_pending.set(true);
+        Some(self.callback_pending.clone())
+    }
+}
+
+#[test]
+fn animation_progress() {
+    let now = Instant::now();
+    let mut animations = Animations::default();
+    animations.start(0, Duration::from_millis(200), now);
+    assert_eq!(animations.progress(0, now), Some(0.0));
+    assert_eq!(
+        animations.progress(0, now + Duration::from_millis(50)),
+        Some(0.25)
+    );
+    assert_eq!(animations.progress(1, now), None);
+}
+
+#[test]
+fn finished_animations_stop() {
+    let now = Instant::now();
+    let mut animations = Animations::default();
+    animations.start(0, Duration::from_millis(100), now);
+    animations.start(1, Duration::from_millis(200), now);
+
+    animations.tick(now + Duration::from_millis(150));
+    assert_eq!(animations.progress(0, now), None);
+    assert!(animations.is_running());
+
+    animations.tick(now + Duration::from_millis(200));
+    assert!(!animations.is_running());
+}
+
+#[test]
+fn restart_animation() {
+    let now = Instant::now();
+    let mut animations = Animations::default();
+    animations.start(0, Duration::from_millis(100), now);
+    animations.start(
+        0,
+        Duration::from_millis(100),
+        now + Duration::from_millis(50),
+    );
+    assert_eq!(
+        animations.progress(0, now + Duration::from_millis(100)),
+        Some(0.5)
+    );
+}
+
+#[test]
+fn cleared_animations_stop() {
+    let now = Instant::now();
+    let mut animations = Animations::default();
+    animations.start(0, Duration::from_millis(200), now);
+    animations.clear();
+    assert!(!animations.is_running());
+    assert_eq!(animations.progress(0, now), None);
+}
+
+#[test]
+fn hidden_while_animating() {
+    let now = Instant::now();
+    let mut animations = Animations::default();
+    animations.start(0, Duration::from_millis(150), now);
+    let stale = animations.request_callback().unwrap();
+    assert!(animations.request_callback().is_none());
+
+    // Hidden, the surface and its callback are gone. Shown again, the animation gets a new one.
+    animations.clear();
+    animations.start(0, Duration::from_millis(150), now);
+    assert!(animations.request_callback().is_some());
+
+    // A late callback of the old surface doesn't stand in for the new one.
+    stale.set(false);
+    assert!(animations.request_callback().is_none());
+}
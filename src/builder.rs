@@ -0,0 +1,283 @@
+//! Validated construction of an [`AdwaitaConfig`].
+use std::{error, fmt};
+
+use smithay_client_toolkit::reexports::{
+    client::{protocol::wl_shm, Attached},
+    protocols::{
+        misc::server_decoration::client::org_kde_kwin_server_decoration_manager::OrgKdeKwinServerDecorationManager,
+        viewporter::client::wp_viewporter::WpViewporter,
+    },
+};
+
+use crate::{
+    buttons::BUTTON_SIZE, theme::ColorTheme, AdwaitaConfig, AdwaitaShell, AxisMaximizeCallback,
+    ButtonKind, ButtonScroll, DecorationEventCallback, HeaderMode, LogicalRect, PixelFormat,
+    RedrawErrorCallback, RedrawMetricsCallback, TitlebarAxisCallback,
+};
+
+/// Why [`AdwaitaConfigBuilder::build`] rejected a configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// A button appears more than once in the layout.
+    DuplicateButton(ButtonKind),
+    /// The corner radius is negative, not finite or doesn't fit in the header.
+    CornerRadius(f32),
+    /// The header is lower than the buttons.
+    HeaderHeight(u32),
+    /// The borders have no width, leaving nothing to resize the window from.
+    BorderSize,
+    /// The title font description is blank.
+    EmptyTitleFont,
+    /// The scale factor isn't a positive number.
+    ScaleFactor(f64),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::DuplicateButton(kind) => {
+                write!(f, "{kind:?} button appears more than once in the layout")
+            }
+            ConfigError::CornerRadius(radius) => {
+                write!(
+                    f,
+                    "corner radius {radius} is not between 0 and the header height"
+                )
+            }
+            ConfigError::HeaderHeight(height) => {
+                write!(f, "header height {height} is lower than the buttons")
+            }
+            ConfigError::BorderSize => write!(f, "border size is zero"),
+            ConfigError::EmptyTitleFont => write!(f, "title font description is empty"),
+            ConfigError::ScaleFactor(scale) => write!(f, "scale factor {scale} is not positive"),
+        }
+    }
+}
+
+impl error::Error for ConfigError {}
+
+/// Builds an [`AdwaitaConfig`], see [`AdwaitaConfig::builder`].
+#[derive(Debug, Clone)]
+pub struct AdwaitaConfigBuilder {
+    config: AdwaitaConfig,
+}
+
+impl AdwaitaConfigBuilder {
+    pub(crate) fn new(theme: ColorTheme) -> Self {
+        Self {
+            config: AdwaitaConfig::with_theme(theme),
+        }
+    }
+
+    pub fn theme(mut self, theme: ColorTheme) -> Self {
+        self.config.theme = theme;
+        self
+    }
+
+    pub fn theme_override(mut self, theme: ColorTheme) -> Self {
+        self.config.theme_override = Some(theme);
+        self
+    }
+
+    pub fn button_layout(mut self, layout: impl Into<Vec<ButtonKind>>) -> Self {
+        self.config.button_layout = layout.into();
+        self
+    }
+
+    pub fn corner_radius(mut self, radius: f32) -> Self {
+        self.config.corner_radius = radius;
+        self
+    }
+
+    pub fn title_font(mut self, font: impl Into<String>) -> Self {
+        self.config.title_font = Some(font.into());
+        self
+    }
+
+    pub fn header_height(mut self, height: u32) -> Self {
+        self.config.header_height = height;
+        self
+    }
+
+    pub fn border_size(mut self, size: u32) -> Self {
+        self.config.border_size = size;
+        self
+    }
+
+    pub fn header_mode(mut self, mode: HeaderMode) -> Self {
+        self.config.header_mode = mode;
+        self
+    }
+
+    pub fn drag_regions(mut self, regions: impl Into<Vec<LogicalRect>>) -> Self {
+        self.config.drag_regions = regions.into();
+        self
+    }
+
+    pub fn reserved_area(mut self, area: LogicalRect) -> Self {
+        self.config.reserved_area = Some(area);
+        self
+    }
+
+    pub fn axis_maximize(mut self, callback: AxisMaximizeCallback) -> Self {
+        self.config.axis_maximize = Some(callback);
+        self
+    }
+
+    pub fn titlebar_axis(mut self, callback: TitlebarAxisCallback) -> Self {
+        self.config.titlebar_axis = Some(callback);
+        self
+    }
+
+    pub fn button_scroll(mut self, button_scroll: ButtonScroll) -> Self {
+        self.config.button_scroll = button_scroll;
+        self
+    }
+
+    pub fn redraw_metrics(mut self, callback: RedrawMetricsCallback) -> Self {
+        self.config.redraw_metrics = Some(callback);
+        self
+    }
+
+    pub fn redraw_error(mut self, callback: RedrawErrorCallback) -> Self {
+        self.config.redraw_error = Some(callback);
+        self
+    }
+
+    pub fn decoration_events(mut self, callback: DecorationEventCallback) -> Self {
+        self.config.decoration_events = Some(callback);
+        self
+    }
+
+    pub fn viewporter(mut self, viewporter: Attached<WpViewporter>) -> Self {
+        self.config.viewporter = Some(viewporter);
+        self
+    }
+
+    pub fn kde_server_decoration(
+        mut self,
+        manager: Attached<OrgKdeKwinServerDecorationManager>,
+    ) -> Self {
+        self.config.kde_server_decoration = Some(manager);
+        self
+    }
+
+    pub fn pixel_format(mut self, format: PixelFormat) -> Self {
+        self.config.pixel_format = format;
+        self
+    }
+
+    pub fn shm_formats(mut self, formats: impl Into<Vec<wl_shm::Format>>) -> Self {
+        self.config.shm_formats = Some(formats.into());
+        self
+    }
+
+    pub fn scale_factor(mut self, scale: f64) -> Self {
+        self.config.scale_factor = Some(scale);
+        self
+    }
+
+    pub fn anti_alias(mut self, anti_alias: bool) -> Self {
+        self.config.anti_alias = anti_alias;
+        self
+    }
+
+    pub fn shadows(mut self, shadows: bool) -> Self {
+        self.config.shadows = shadows;
+        self
+    }
+
+    pub fn suspended(mut self, suspended: bool) -> Self {
+        self.config.suspended = suspended;
+        self
+    }
+
+    pub fn animate_visibility(mut self, animate: bool) -> Self {
+        self.config.animate_visibility = animate;
+        self
+    }
+
+    pub fn shell(mut self, shell: AdwaitaShell) -> Self {
+        self.config.shell = Some(shell);
+        self
+    }
+
+    /// The configuration, unless one of its values can't be drawn.
+    pub fn build(self) -> Result<AdwaitaConfig, ConfigError> {
+        let config = self.config;
+
+        for (id, kind) in config.button_layout.iter().enumerate() {
+            if config.button_layout[..id].contains(kind) {
+                return Err(ConfigError::DuplicateButton(*kind));
+            }
+        }
+
+        if (config.header_height as f32) < BUTTON_SIZE {
+            return Err(ConfigError::HeaderHeight(config.header_height));
+        }
+
+        if config.border_size == 0 {
+            return Err(ConfigError::BorderSize);
+        }
+
+        let radius = config.corner_radius;
+        if !(0.0..=config.header_height as f32).contains(&radius) {
+            return Err(ConfigError::CornerRadius(radius));
+        }
+
+        if matches!(&config.title_font, Some(font) if font.trim().is_empty()) {
+            return Err(ConfigError::EmptyTitleFont);
+        }
+
+        let invalid = |scale: &f64| !(scale.is_finite() && *scale > 0.0);
+        if let Some(scale) = config.scale_factor.filter(invalid) {
+            return Err(ConfigError::ScaleFactor(scale));
+        }
+
+        Ok(config)
+    }
+}
+
+#[test]
+fn nonsense_values_are_rejected() {
+    let builder = AdwaitaConfig::builder();
+    assert!(builder.clone().build().is_ok());
+
+    let layout = [ButtonKind::Close, ButtonKind::Minimize, ButtonKind::Close];
+    assert_eq!(
+        builder.clone().button_layout(layout).build().unwrap_err(),
+        ConfigError::DuplicateButton(ButtonKind::Close)
+    );
+
+    let too_large = crate::theme::HEADER_SIZE as f32 + 1.0;
+    for radius in [-1.0, f32::NAN, f32::INFINITY, too_large] {
+        let error = builder.clone().corner_radius(radius).build().unwrap_err();
+        assert!(matches!(error, ConfigError::CornerRadius(_)));
+    }
+
+    assert_eq!(
+        builder.clone().header_height(20).build().unwrap_err(),
+        ConfigError::HeaderHeight(20)
+    );
+    assert_eq!(
+        builder.clone().border_size(0).build().unwrap_err(),
+        ConfigError::BorderSize
+    );
+    assert!(builder
+        .clone()
+        .header_height(48)
+        .corner_radius(40.0)
+        .build()
+        .is_ok());
+
+    assert_eq!(
+        builder.clone().title_font(" ").build().unwrap_err(),
+        ConfigError::EmptyTitleFont
+    );
+
+    for scale in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        let error = builder.clone().scale_factor(scale).build().unwrap_err();
+        assert!(matches!(error, ConfigError::ScaleFactor(_)));
+    }
+    assert!(builder.scale_factor(1.5).build().is_ok());
+}
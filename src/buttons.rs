@@ -1,19 +1,66 @@
+use std::{
+    cell::RefCell,
+    collections::{hash_map::Entry, HashMap},
+    rc::Rc,
+};
+
 use smithay_client_toolkit::window::ButtonState;
-use tiny_skia::{FillRule, PathBuilder, PixmapMut, Rect, Stroke, Transform};
+use tiny_skia::{
+    Color, FillRule, PathBuilder, Pixmap, PixmapMut, PixmapPaint, Rect, Stroke, Transform,
+};
 
 use crate::{
-    theme::{ColorMap, BORDER_SIZE},
+    theme::{ColorMap, Sizes},
     Location, SkiaResult,
 };
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Diameter of the buttons in logical pixels.
+pub(crate) const BUTTON_SIZE: f32 = 24.0;
+
+/// A titlebar button.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum ButtonKind {
     Close,
     Maximize,
     Minimize,
 }
 
-#[derive(Default, Debug)]
+impl ButtonKind {
+    /// All buttons, in the order they are drawn and of their discriminants.
+    pub const ALL: [ButtonKind; 3] = [
+        ButtonKind::Close,
+        ButtonKind::Maximize,
+        ButtonKind::Minimize,
+    ];
+
+    /// The buttons on the right of the GNOME `button-layout` setting, in the order of
+    /// `AdwaitaConfig::button_layout`.
+    ///
+    /// Buttons on the left aren't supported and left out.
+    pub fn system_layout() -> Option<Vec<ButtonKind>> {
+        crate::config::button_layout().value
+    }
+}
+
+/// Parses a layout like `appmenu:minimize,maximize,close`, unknown entries are skipped.
+pub(crate) fn parse_layout(layout: &str) -> Vec<ButtonKind> {
+    let right = layout.split_once(':').map_or("", |(_, right)| right);
+    let mut buttons = Vec::new();
+    for name in right.split(',').rev() {
+        let kind = match name.trim() {
+            "close" => ButtonKind::Close,
+            "maximize" => ButtonKind::Maximize,
+            "minimize" => ButtonKind::Minimize,
+            _ => continue,
+        };
+        if !buttons.contains(&kind) {
+            buttons.push(kind);
+        }
+    }
+    buttons
+}
+
+#[derive(Default, Debug, Clone)]
 pub(crate) struct Button {
     x: f32,
     y: f32,
@@ -37,6 +84,10 @@ impl Button {
         self.y + self.radius()
     }
 
+    pub fn rect(&self) -> Option<Rect> {
+        Rect::from_xywh(self.x, self.y, self.size, self.size)
+    }
+
     fn contains(&self, x: f32, y: f32) -> bool {
         x > self.x && x < self.x + self.size && y > self.y && y < self.y + self.size
     }
@@ -47,10 +98,11 @@ impl Button {
         &self,
         scale: f32,
         colors: &ColorMap,
-        mouses: &[Location],
+        hovered: bool,
+        anti_alias: bool,
         pixmap: &mut PixmapMut,
     ) -> SkiaResult {
-        let btn_state = if mouses.contains(&Location::Button(ButtonKind::Minimize)) {
+        let btn_state = if hovered {
             ButtonState::Hovered
         } else {
             ButtonState::Idle
@@ -63,11 +115,12 @@ impl Button {
 
         let circle = PathBuilder::from_circle(x, y, radius)?;
 
-        let button_bg = if btn_state == ButtonState::Hovered {
+        let mut button_bg = if btn_state == ButtonState::Hovered {
             colors.button_hover_paint()
         } else {
             colors.button_idle_paint()
         };
+        button_bg.anti_alias = anti_alias;
 
         pixmap.fill_path(
             &circle,
@@ -92,21 +145,20 @@ impl Button {
         Some(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn draw_maximize(
         &self,
         scale: f32,
         colors: &ColorMap,
-        mouses: &[Location],
+        hovered: bool,
         maximizable: bool,
         is_maximized: bool,
+        anti_alias: bool,
         pixmap: &mut PixmapMut,
     ) -> SkiaResult {
         let btn_state = if !maximizable {
             ButtonState::Disabled
-        } else if mouses
-            .iter()
-            .any(|&l| l == Location::Button(ButtonKind::Maximize))
-        {
+        } else if hovered {
             ButtonState::Hovered
         } else {
             ButtonState::Idle
@@ -123,11 +175,12 @@ impl Button {
             pb.finish()?
         };
 
-        let button_bg = if btn_state == ButtonState::Hovered {
+        let mut button_bg = if btn_state == ButtonState::Hovered {
             colors.button_hover_paint()
         } else {
             colors.button_idle_paint()
         };
+        button_bg.anti_alias = anti_alias;
 
         pixmap.fill_path(
             &path1,
@@ -138,7 +191,7 @@ impl Button {
         );
 
         let path2 = {
-            let size = 8.0 * scale as f32;
+            let size = 8.0 * scale;
             let hsize = size / 2.0;
             let mut pb = PathBuilder::new();
 
@@ -163,7 +216,7 @@ impl Button {
             &path2,
             &button_icon_paint,
             &Stroke {
-                width: 1.0 * scale as f32,
+                width: 1.0 * scale,
                 ..Default::default()
             },
             Transform::identity(),
@@ -177,14 +230,12 @@ impl Button {
         &self,
         scale: f32,
         colors: &ColorMap,
-        mouses: &[Location],
+        hovered: bool,
+        anti_alias: bool,
         pixmap: &mut PixmapMut,
     ) -> SkiaResult {
         // Draw the close button
-        let btn_state = if mouses
-            .iter()
-            .any(|&l| l == Location::Button(ButtonKind::Close))
-        {
+        let btn_state = if hovered {
             ButtonState::Hovered
         } else {
             ButtonState::Idle
@@ -201,11 +252,12 @@ impl Button {
             pb.finish()?
         };
 
-        let button_bg = if btn_state == ButtonState::Hovered {
+        let mut button_bg = if btn_state == ButtonState::Hovered {
             colors.button_hover_paint()
         } else {
             colors.button_idle_paint()
         };
+        button_bg.anti_alias = anti_alias;
 
         pixmap.fill_path(
             &path1,
@@ -216,7 +268,11 @@ impl Button {
         );
 
         let x_icon = {
-            let size = 3.5 * scale as f32;
+            let size = if anti_alias {
+                3.5 * scale
+            } else {
+                (3.5 * scale).round()
+            };
             let mut pb = PathBuilder::new();
 
             {
@@ -245,12 +301,17 @@ impl Button {
         };
 
         let mut button_icon_paint = colors.button_icon_paint();
-        button_icon_paint.anti_alias = true;
+        button_icon_paint.anti_alias = anti_alias;
+        let width = if anti_alias {
+            1.1 * scale
+        } else {
+            (1.1 * scale).round()
+        };
         pixmap.stroke_path(
             &x_icon,
             &button_icon_paint,
             &Stroke {
-                width: 1.1 * scale as f32,
+                width,
                 ..Default::default()
             },
             Transform::identity(),
@@ -261,14 +322,116 @@ impl Button {
     }
 }
 
-#[derive(Debug)]
+/// Everything a rasterized button depends on, besides the colors.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct IconKey {
+    pub kind: ButtonKind,
+    pub scale: u32,
+    pub active: bool,
+    pub hovered: bool,
+    pub maximizable: bool,
+    pub maximized: bool,
+    pub anti_alias: bool,
+}
+
+/// Button colors an icon was drawn with, as RGBA bytes.
+type IconColors = [[u8; 4]; 3];
+
+thread_local! {
+    static SHARED_ICONS: ButtonIcons = ButtonIcons::default();
+}
+
+/// Rasterized buttons, so hover changes only blit a pixmap instead of filling paths.
+///
+/// Icons are keyed by their colors too, so theme changes never hit stale icons.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ButtonIcons {
+    icons: Rc<RefCell<HashMap<(IconKey, IconColors), Pixmap>>>,
+}
+
+impl ButtonIcons {
+    /// The cache shared by all frames of this thread, so multi-window apps rasterize each icon
+    /// once.
+    pub fn shared() -> Self {
+        SHARED_ICONS.with(Clone::clone)
+    }
+
+    pub fn draw(
+        &self,
+        pixmap: &mut PixmapMut,
+        button: &Button,
+        key: IconKey,
+        colors: &ColorMap,
+    ) -> SkiaResult {
+        let rgba = |color: Color| {
+            let color = color.to_color_u8();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        };
+        let icon_colors = [
+            rgba(colors.button_idle),
+            rgba(colors.button_hover),
+            rgba(colors.button_icon),
+        ];
+
+        let mut icons = self.icons.borrow_mut();
+        let icon = match icons.entry((key, icon_colors)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(render_icon(button.size, key, colors)?),
+        };
+
+        pixmap.draw_pixmap(
+            button.x as i32,
+            button.y as i32,
+            icon.as_ref(),
+            &PixmapPaint::default(),
+            Transform::identity(),
+            None,
+        );
+
+        Some(())
+    }
+}
+
+fn render_icon(size: f32, key: IconKey, colors: &ColorMap) -> Option<Pixmap> {
+    let mut pixmap = Pixmap::new(size.ceil() as u32, size.ceil() as u32)?;
+    let button = Button {
+        x: 0.0,
+        y: 0.0,
+        size,
+    };
+
+    let scale = key.scale as f32;
+    let canvas = &mut pixmap.as_mut();
+    match key.kind {
+        ButtonKind::Close => button.draw_close(scale, colors, key.hovered, key.anti_alias, canvas),
+        ButtonKind::Maximize => button.draw_maximize(
+            scale,
+            colors,
+            key.hovered,
+            key.maximizable,
+            key.maximized,
+            key.anti_alias,
+            canvas,
+        ),
+        ButtonKind::Minimize => {
+            button.draw_minimize(scale, colors, key.hovered, key.anti_alias, canvas)
+        }
+    }?;
+
+    Some(pixmap)
+}
+
+#[derive(Debug, Clone)]
 pub(crate) struct Buttons {
     pub close: Button,
     pub maximize: Button,
     pub minimize: Button,
 
+    /// Shown buttons, from right to left.
+    layout: Vec<ButtonKind>,
+
     w: u32,
-    h: u32,
+    sizes: Sizes,
 
     scale: u32,
 }
@@ -279,10 +442,11 @@ impl Default for Buttons {
             close: Default::default(),
             maximize: Default::default(),
             minimize: Default::default(),
+            layout: ButtonKind::ALL.to_vec(),
             scale: 1,
 
             w: 0,
-            h: super::theme::HEADER_SIZE,
+            sizes: Sizes::default(),
         }
     }
 }
@@ -292,30 +456,96 @@ impl Buttons {
         self.w = w;
 
         let scale = self.scale as f32;
-        let margin_top = BORDER_SIZE as f32 * scale;
+        let border = self.sizes.border as f32 * scale;
         let margin = 5.0 * scale;
         let spacing = 13.0 * scale;
-        let size = 12.0 * 2.0 * scale;
-
-        let mut x = w as f32 * scale - margin - BORDER_SIZE as f32 * scale;
-        let y = margin + margin_top;
-
-        x -= size;
-        self.close.x = x;
-        self.close.y = y;
-        self.close.size = size;
-
-        x -= size;
-        x -= spacing;
-        self.maximize.x = x;
-        self.maximize.y = y;
-        self.maximize.size = size;
-
-        x -= size;
-        x -= spacing;
-        self.minimize.x = x;
-        self.minimize.y = y;
-        self.minimize.size = size;
+        let size = BUTTON_SIZE * scale;
+
+        // Vertically centered, rounded down to whole logical pixels.
+        let margin_top = ((self.sizes.header as f32 - BUTTON_SIZE) / 2.0).floor() * scale;
+
+        let mut x = w as f32 * scale - margin - border + spacing;
+        let y = border + margin_top;
+
+        for id in 0..self.layout.len() {
+            x -= size + spacing;
+            let button = self.get_mut(self.layout[id]);
+            button.x = x;
+            button.y = y;
+            button.size = size;
+        }
+    }
+
+    /// Sizes of the header and borders, the buttons are laid out in.
+    pub fn sizes(&self) -> Sizes {
+        self.sizes
+    }
+
+    /// Changes the sizes, keeping the width of the window content.
+    pub fn set_sizes(&mut self, sizes: Sizes) {
+        if self.sizes != sizes {
+            let content_width = self.w.saturating_sub(2 * self.sizes.border);
+            self.sizes = sizes;
+            self.arrange(content_width + 2 * sizes.border);
+        }
+    }
+
+    /// Shows the buttons of `layout`, from right to left.
+    pub fn set_layout(&mut self, layout: Vec<ButtonKind>) {
+        if self.layout != layout {
+            self.layout = layout;
+            self.arrange(self.w);
+        }
+    }
+
+    /// Left edge of the leftmost shown button, or the right edge of the header without buttons.
+    pub fn left_edge(&self) -> f32 {
+        self.layout
+            .iter()
+            .filter(|&&kind| self.fits(kind))
+            .map(|&kind| self.get(kind).x())
+            .fold(
+                self.w.saturating_sub(self.sizes.border) as f32 * self.scale as f32,
+                f32::min,
+            )
+    }
+
+    /// Logical rectangle `(x, y, width, height)` on the header from just left of the shown
+    /// buttons to the right border.
+    pub fn cluster(&self) -> (i32, i32, i32, i32) {
+        let margin = 5.0;
+        let left = (self.left_edge() / self.scale as f32 - margin)
+            .floor()
+            .max(0.0) as i32;
+        let right = self.w.saturating_sub(self.sizes.border) as i32;
+        let Sizes { header, border } = self.sizes;
+        (left, border as i32, (right - left).max(0), header as i32)
+    }
+
+    pub fn get(&self, kind: ButtonKind) -> &Button {
+        match kind {
+            ButtonKind::Close => &self.close,
+            ButtonKind::Maximize => &self.maximize,
+            ButtonKind::Minimize => &self.minimize,
+        }
+    }
+
+    fn get_mut(&mut self, kind: ButtonKind) -> &mut Button {
+        match kind {
+            ButtonKind::Close => &mut self.close,
+            ButtonKind::Maximize => &mut self.maximize,
+            ButtonKind::Minimize => &mut self.minimize,
+        }
+    }
+
+    /// Whether `kind` is part of the layout and fits in front of the left border, buttons which
+    /// don't are neither drawn nor clickable.
+    pub fn fits(&self, kind: ButtonKind) -> bool {
+        self.layout.contains(&kind) && self.get(kind).x > (self.sizes.border * self.scale) as f32
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
     }
 
     pub fn update_scale(&mut self, scale: u32) {
@@ -328,18 +558,151 @@ impl Buttons {
     pub fn find_button(&self, x: f64, y: f64) -> Location {
         let x = x as f32 * self.scale as f32;
         let y = y as f32 * self.scale as f32;
-        if self.close.contains(x, y) {
-            Location::Button(ButtonKind::Close)
-        } else if self.maximize.contains(x, y) {
-            Location::Button(ButtonKind::Maximize)
-        } else if self.minimize.contains(x, y) {
-            Location::Button(ButtonKind::Minimize)
-        } else {
-            Location::Head
-        }
+        ButtonKind::ALL
+            .into_iter()
+            .find(|&kind| self.fits(kind) && self.get(kind).contains(x, y))
+            .map_or(Location::Head, Location::Button)
     }
 
     pub fn scaled_size(&self) -> (u32, u32) {
-        (self.w * self.scale as u32, self.h * self.scale as u32)
+        (
+            self.w.saturating_mul(self.scale),
+            self.sizes.header.saturating_mul(self.scale),
+        )
     }
 }
+
+#[test]
+fn gnome_button_layout() {
+    use ButtonKind::*;
+    assert_eq!(
+        parse_layout("appmenu:minimize,maximize,close"),
+        [Close, Maximize, Minimize]
+    );
+    assert_eq!(parse_layout("appmenu:close"), [Close]);
+    assert_eq!(parse_layout("close,minimize:spacer,maximize"), [Maximize]);
+    assert_eq!(parse_layout("close:close"), [Close]);
+    assert_eq!(parse_layout("close,maximize"), []);
+}
+
+#[test]
+fn icons_are_cached_per_state() {
+    let colors = crate::theme::ColorTheme::light().active;
+    let mut buttons = Buttons::default();
+    buttons.arrange(200);
+    let icons = ButtonIcons::default();
+    let mut pixmap = Pixmap::new(200, 40).unwrap();
+
+    let key = IconKey {
+        kind: ButtonKind::Close,
+        scale: 1,
+        active: true,
+        hovered: false,
+        maximizable: true,
+        maximized: false,
+        anti_alias: true,
+    };
+    icons.draw(&mut pixmap.as_mut(), &buttons.close, key, &colors);
+    icons.draw(&mut pixmap.as_mut(), &buttons.close, key, &colors);
+    assert_eq!(icons.icons.borrow().len(), 1);
+
+    let hovered = IconKey {
+        hovered: true,
+        ..key
+    };
+    icons.draw(&mut pixmap.as_mut(), &buttons.close, hovered, &colors);
+    assert_eq!(icons.icons.borrow().len(), 2);
+}
+
+#[test]
+fn cached_icon_matches_direct_draw() {
+    let colors = crate::theme::ColorTheme::light().active;
+    let mut buttons = Buttons::default();
+    buttons.arrange(200);
+
+    let mut direct = Pixmap::new(200, 40).unwrap();
+    buttons
+        .minimize
+        .draw_minimize(1.0, &colors, false, true, &mut direct.as_mut())
+        .unwrap();
+
+    let mut cached = Pixmap::new(200, 40).unwrap();
+    let key = IconKey {
+        kind: ButtonKind::Minimize,
+        scale: 1,
+        active: true,
+        hovered: false,
+        maximizable: true,
+        maximized: false,
+        anti_alias: true,
+    };
+    ButtonIcons::default()
+        .draw(&mut cached.as_mut(), &buttons.minimize, key, &colors)
+        .unwrap();
+
+    assert_eq!(direct.data(), cached.data());
+}
+
+#[test]
+fn icons_are_shared_between_frames() {
+    let colors = crate::theme::ColorTheme::light().active;
+    let mut buttons = Buttons::default();
+    buttons.arrange(200);
+    let mut pixmap = Pixmap::new(200, 40).unwrap();
+
+    let key = IconKey {
+        kind: ButtonKind::Maximize,
+        scale: 1,
+        active: true,
+        hovered: false,
+        maximizable: true,
+        maximized: false,
+        anti_alias: true,
+    };
+    ButtonIcons::shared().draw(&mut pixmap.as_mut(), &buttons.maximize, key, &colors);
+    assert_eq!(ButtonIcons::shared().icons.borrow().len(), 1);
+
+    // Different colors must not hit the icon drawn with the light theme.
+    let dark = crate::theme::ColorTheme::dark().active;
+    ButtonIcons::shared().draw(&mut pixmap.as_mut(), &buttons.maximize, key, &dark);
+    assert_eq!(ButtonIcons::shared().icons.borrow().len(), 2);
+}
+
+#[test]
+fn buttons_are_dropped_from_the_left() {
+    let mut buttons = Buttons::default();
+    let fitting = |buttons: &Buttons| ButtonKind::ALL.map(|kind| buttons.fits(kind));
+
+    buttons.arrange(200);
+    assert_eq!(fitting(&buttons), [true, true, true]);
+    buttons.arrange(100);
+    assert_eq!(fitting(&buttons), [true, true, false]);
+    buttons.arrange(60);
+    assert_eq!(fitting(&buttons), [true, false, false]);
+    buttons.arrange(2 * crate::theme::BORDER_SIZE);
+    assert_eq!(fitting(&buttons), [false, false, false]);
+
+    // Buttons which aren't drawn can't be clicked either.
+    let close = &buttons.close;
+    let location = buttons.find_button(close.center_x().into(), close.center_y().into());
+    assert_eq!(location, Location::Head);
+}
+
+#[test]
+fn layout_selects_and_orders_buttons() {
+    let mut buttons = Buttons::default();
+    buttons.arrange(200);
+    let close_x = buttons.close.x();
+
+    buttons.set_layout(vec![ButtonKind::Minimize, ButtonKind::Close]);
+    assert_eq!(buttons.minimize.x(), close_x);
+    assert!(buttons.close.x() < close_x);
+    assert!(!buttons.fits(ButtonKind::Maximize));
+    assert_eq!(buttons.left_edge(), buttons.close.x());
+
+    buttons.set_layout(Vec::new());
+    assert_eq!(
+        buttons.left_edge(),
+        (200 - crate::theme::BORDER_SIZE) as f32
+    );
+}
@@ -0,0 +1,228 @@
+//! Putting drawn decorations on their surfaces: buffers, positions, transforms and damage.
+//!
+//! What is drawn is decided by the frame and drawn by [`core`](crate::core), the surfaces are
+//! managed by [`parts`](crate::parts).
+use std::cell::RefCell;
+
+use smithay_client_toolkit::reexports::client::{
+    protocol::{wl_compositor, wl_output, wl_surface},
+    Attached,
+};
+use tiny_skia::{IntRect, Pixmap};
+
+use crate::{
+    buttons::Buttons,
+    error::{buffer_stride, DecorationError},
+    parts::Part,
+    pixel::{apply_opacity, PixelFormat},
+    pool::Pool,
+    transform, DecorationPart, Edges, HeaderMode, RedrawMetrics, SkiaResult, WindowState,
+};
+
+/// Attaches the buffers of one redraw.
+pub(crate) struct Composer<'a> {
+    pub pool: &'a RefCell<Pool>,
+    pub pixel_format: PixelFormat,
+    pub surface_version: u32,
+    /// Opacity of all parts, while the decorations fade in.
+    pub opacity: f32,
+    pub metrics: &'a mut RedrawMetrics,
+}
+
+/// Where and how a buffer is shown.
+pub(crate) struct Placement {
+    pub kind: DecorationPart,
+    /// Size of the buffer before `transform`.
+    pub size: (u32, u32),
+    pub transform: wl_output::Transform,
+    /// Position of the part relative to the window content.
+    pub position: (i32, i32),
+    /// Size of the part in surface coordinates, stretched to by its viewport.
+    pub surface_size: (u32, u32),
+    /// Changed area in transformed buffer pixels and in surface coordinates, all of the part
+    /// if `None`.
+    pub damage: Option<(IntRect, IntRect)>,
+}
+
+impl Composer<'_> {
+    /// Draws a buffer with `draw`, which gets the transformed canvas, and commits it to `part`.
+    pub fn commit(
+        &mut self,
+        part: &Part,
+        placement: Placement,
+        draw: impl FnOnce(&mut [u8]) -> Result<(), DecorationError>,
+    ) -> Result<(), DecorationError> {
+        let (width, height) = placement.size;
+        let (buffer_width, buffer_height) =
+            transform::buffer_size(placement.transform, width, height);
+        let stride = buffer_stride(buffer_width, buffer_height)?;
+        let (opacity, pixel_format) = (self.opacity, self.pixel_format);
+        let buffer = self.pool.borrow_mut().try_draw::<_, DecorationError>(
+            buffer_width as i32,
+            buffer_height as i32,
+            stride,
+            pixel_format.shm_format(),
+            |canvas| {
+                draw(canvas)?;
+                #[cfg(feature = "debug-tint")]
+                debug_tint(canvas, buffer_width, buffer_height, placement.kind);
+                apply_opacity(canvas, opacity);
+                pixel_format.convert(canvas);
+                Ok(())
+            },
+        )?;
+
+        let (x, y) = placement.position;
+        part.subsurface.set_position(x, y);
+        let (surface_width, surface_height) = placement.surface_size;
+        if let Some(viewport) = part.viewport.as_ref() {
+            viewport.set_destination(surface_width as i32, surface_height as i32);
+        }
+        if self.surface_version >= 2 {
+            part.surface.set_buffer_transform(placement.transform);
+        }
+
+        let (buffer_damage, surface_damage) = match placement.damage {
+            Some(damage) => damage,
+            None => (
+                IntRect::from_xywh(0, 0, buffer_width, buffer_height)
+                    .ok_or(DecorationError::Render)?,
+                IntRect::from_xywh(0, 0, surface_width, surface_height)
+                    .ok_or(DecorationError::Render)?,
+            ),
+        };
+        self.metrics
+            .record_buffer(placement.kind, buffer_width, buffer_height, buffer_damage);
+        part.surface.attach(Some(&buffer), 0, 0);
+        damage_surface(
+            &part.surface,
+            self.surface_version,
+            buffer_damage,
+            surface_damage,
+        );
+        part.surface.commit();
+        Ok(())
+    }
+}
+
+/// Everything the header content depends on, besides the title, theme and hovered buttons.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct HeaderState {
+    pub width: u32,
+    pub height: u32,
+    pub scale: u32,
+    pub active: WindowState,
+    pub resizable: bool,
+    pub maximized: bool,
+    pub tiled: Edges,
+    pub shadow: bool,
+    pub fade: Option<u16>,
+    pub appear: Option<u16>,
+}
+
+/// The last rendered header, used to only redraw buttons on hover changes.
+///
+/// Borders are drawn straight into the shm canvas, the header can't be: buffers handed out by
+/// the pool have undefined content, so hover updates need the previous frame to start from.
+#[derive(Debug)]
+pub(crate) struct HeaderCache {
+    /// `None` if the pixmap is out of date.
+    pub state: Option<HeaderState>,
+    pub hovered: [bool; 3],
+    pub pixmap: Pixmap,
+}
+
+/// Everything the border content depends on, besides the theme.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct BorderState {
+    pub size: (u32, u32),
+    pub scales: [u32; 3],
+    pub transforms: [wl_output::Transform; 3],
+    pub hidden: Edges,
+    pub active: WindowState,
+    pub shadow: bool,
+    pub fade: Option<u16>,
+    pub appear: Option<u16>,
+}
+
+/// Damages a decoration surface, `buffer_damage` and `surface_damage` cover the same area in
+/// buffer pixels and in surface coordinates.
+///
+/// `damage_buffer` needs `wl_surface` version 4, older compositors only get the surface
+/// coordinates.
+fn damage_surface(
+    surface: &wl_surface::WlSurface,
+    version: u32,
+    buffer_damage: IntRect,
+    surface_damage: IntRect,
+) {
+    if version >= 4 {
+        surface.damage_buffer(
+            buffer_damage.x(),
+            buffer_damage.y(),
+            buffer_damage.width() as i32,
+            buffer_damage.height() as i32,
+        );
+    } else {
+        surface.damage(
+            surface_damage.x(),
+            surface_damage.y(),
+            surface_damage.width() as i32,
+            surface_damage.height() as i32,
+        );
+    }
+}
+
+/// Floating buttons leave the input over the content to the window, besides the top resize
+/// margin.
+pub(crate) fn set_header_input_region(
+    surface: &wl_surface::WlSurface,
+    compositor: &Attached<wl_compositor::WlCompositor>,
+    mode: HeaderMode,
+    buttons: &Buttons,
+) {
+    match mode {
+        HeaderMode::Full => surface.set_input_region(None),
+        HeaderMode::ButtonsOnly => {
+            let region = compositor.create_region();
+            let (width, _) = buttons.scaled_size();
+            let width = width / buttons.scale();
+            region.add(0, 0, width as i32, buttons.sizes().border as i32);
+            let (x, y, w, h) = buttons.cluster();
+            region.add(x, y, w, h);
+            surface.set_input_region(Some(&region));
+            region.destroy();
+        }
+    }
+}
+
+/// Covers a buffer of `kind` in its own translucent color, to tell the surfaces apart.
+#[cfg(feature = "debug-tint")]
+fn debug_tint(canvas: &mut [u8], width: u32, height: u32, kind: DecorationPart) {
+    let color = match kind {
+        DecorationPart::Header => tiny_skia::Color::from_rgba8(255, 0, 0, 64),
+        DecorationPart::Left => tiny_skia::Color::from_rgba8(0, 255, 0, 64),
+        DecorationPart::Right => tiny_skia::Color::from_rgba8(0, 0, 255, 64),
+        DecorationPart::Bottom => tiny_skia::Color::from_rgba8(255, 0, 255, 64),
+    };
+    let mut paint = tiny_skia::Paint::default();
+    paint.set_color(color);
+    if let (Some(mut pixmap), Some(rect)) = (
+        tiny_skia::PixmapMut::from_bytes(canvas, width, height),
+        tiny_skia::Rect::from_xywh(0.0, 0.0, width as f32, height as f32),
+    ) {
+        pixmap.fill_rect(rect, &paint, tiny_skia::Transform::identity(), None);
+    }
+}
+
+/// Unmaps a decoration surface.
+pub(crate) fn clear_part(part: &Part) {
+    part.surface.attach(None, 0, 0);
+    part.surface.commit();
+}
+
+/// Buffers of failed draws are freed right away by `AutoMemPool::try_draw`, which needs the
+/// failure as an error.
+pub(crate) fn draw_result(result: SkiaResult) -> Result<(), DecorationError> {
+    result.ok_or(DecorationError::Render)
+}
@@ -1,19 +1,383 @@
 //! System configuration.
-use std::process::Command;
+//!
+//! Every setting is looked up along the same chain, the first source that has it wins: the
+//! embedder's own [`SettingsProvider`], environment variables, the desktop portal, `gsettings`,
+//! the dconf database and finally the built-in default. Values set explicitly in the
+//! [`AdwaitaConfig`](crate::AdwaitaConfig) take precedence over all of them.
+use std::{env, process::Command};
 
-/// Query system to see if dark theming should be preferred.
-pub(crate) fn prefer_dark() -> bool {
-    // outputs something like: `variant       variant          uint32 1`
-    let stdout = Command::new("dbus-send")
+use crate::{
+    buttons::{parse_layout, ButtonKind},
+    dconf,
+    logging::debug,
+};
+
+/// Where a system setting was read from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SettingsSource {
+    /// The provider passed to [`SystemSettings::read_with`].
+    Provider,
+    /// An environment variable, `GTK_THEME` for the color scheme.
+    Env,
+    /// `org.freedesktop.portal.Settings` of the desktop portal.
+    Portal,
+    /// The `gsettings` tool.
+    Gsettings,
+    /// The user's dconf database, read directly.
+    Dconf,
+    /// None of the sources had the setting.
+    Default,
+}
+
+/// A setting and the source it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolved<T> {
+    pub value: T,
+    pub source: SettingsSource,
+}
+
+/// The system settings the decorations follow, e.g. to find out why one isn't applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemSettings {
+    /// Whether the dark theme is preferred, used by `ColorTheme::auto`.
+    pub prefer_dark: Resolved<bool>,
+    /// Whether animations are enabled, see `AdwaitaConfig::animate_visibility`.
+    pub animations: Resolved<bool>,
+    /// Title font, used unless `AdwaitaConfig::title_font` is set.
+    pub titlebar_font: Resolved<Option<String>>,
+    /// Buttons, see `ButtonKind::system_layout`.
+    pub button_layout: Resolved<Option<Vec<ButtonKind>>>,
+}
+
+impl SystemSettings {
+    /// Reads all settings, which takes a process per source tried.
+    pub fn read() -> Self {
+        Self::resolve(SYSTEM_SOURCES)
+    }
+
+    /// Reads all settings, asking `provider` before the system.
+    pub fn read_with(provider: &dyn SettingsProvider) -> Self {
+        let mut sources = vec![(SettingsSource::Provider, provider)];
+        sources.extend_from_slice(SYSTEM_SOURCES);
+        Self::resolve(&sources)
+    }
+
+    fn resolve(sources: &[Source]) -> Self {
+        Self {
+            prefer_dark: resolve("dark color scheme", sources, |p| p.prefer_dark(), false),
+            animations: resolve("animations", sources, |p| p.animations(), true),
+            titlebar_font: resolve(
+                "titlebar font",
+                sources,
+                |p| p.titlebar_font().map(Some),
+                None,
+            ),
+            button_layout: resolve(
+                "button layout",
+                sources,
+                |p| p.button_layout().map(Some),
+                None,
+            ),
+        }
+    }
+}
+
+/// Where the decorations look up the system settings.
+///
+/// Each method returns `None` if the provider doesn't know about the setting, which leaves it to
+/// the next source. Embedders with their own settings, like a game engine's settings menu, pass
+/// theirs to [`SystemSettings::read_with`].
+///
+/// Only frames configured through an [`AdwaitaShell`](crate::AdwaitaShell) created
+/// [`with_settings`](crate::AdwaitaShell::with_settings) follow such a provider. Without a shell,
+/// [`ColorTheme::auto`](crate::theme::ColorTheme::auto),
+/// [`ButtonKind::system_layout`](crate::ButtonKind::system_layout) and the default title font
+/// read the system sources directly.
+pub trait SettingsProvider {
+    /// Whether the dark theme is preferred.
+    fn prefer_dark(&self) -> Option<bool> {
+        None
+    }
+
+    /// Whether animations are enabled.
+    fn animations(&self) -> Option<bool> {
+        None
+    }
+
+    /// Title font description, like `Cantarell Bold 12`.
+    fn titlebar_font(&self) -> Option<String> {
+        None
+    }
+
+    /// Buttons, in the order of `AdwaitaConfig::button_layout`.
+    fn button_layout(&self) -> Option<Vec<ButtonKind>> {
+        None
+    }
+}
+
+type Source<'a> = (SettingsSource, &'a dyn SettingsProvider);
+
+const SYSTEM_SOURCES: &[Source<'static>] = &[
+    (SettingsSource::Env, &EnvSettings),
+    (SettingsSource::Portal, &PortalSettings),
+    (SettingsSource::Gsettings, &GsettingsSettings),
+    (SettingsSource::Dconf, &DconfSettings),
+];
+
+/// The first of `sources` which has the setting read by `read`, or `default`.
+fn resolve<T: std::fmt::Debug>(
+    name: &'static str,
+    sources: &[Source],
+    read: impl Fn(&dyn SettingsProvider) -> Option<T>,
+    default: T,
+) -> Resolved<T> {
+    sources
+        .iter()
+        .fold(Chain::new(name), |chain, &(source, provider)| {
+            chain.or(source, || read(provider))
+        })
+        .or_default(default)
+}
+
+/// Tries the sources of a setting in order, until one of them has it.
+struct Chain<T> {
+    name: &'static str,
+    resolved: Option<Resolved<T>>,
+}
+
+impl<T: std::fmt::Debug> Chain<T> {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            resolved: None,
+        }
+    }
+
+    fn or(mut self, source: SettingsSource, read: impl FnOnce() -> Option<T>) -> Self {
+        if self.resolved.is_none() {
+            self.resolved = read().map(|value| Resolved { value, source });
+        }
+        self
+    }
+
+    fn or_default(self, value: T) -> Resolved<T> {
+        let resolved = self.resolved.unwrap_or(Resolved {
+            value,
+            source: SettingsSource::Default,
+        });
+        debug!(
+            "Using {} {:?} from {:?}",
+            self.name, resolved.value, resolved.source
+        );
+        resolved
+    }
+}
+
+/// Reads a setting through the desktop portal, as printed by `dbus-send`.
+fn read_setting(namespace: &str, key: &str) -> Option<String> {
+    Command::new("dbus-send")
         .arg("--print-reply=literal")
         .arg("--dest=org.freedesktop.portal.Desktop")
         .arg("/org/freedesktop/portal/desktop")
         .arg("org.freedesktop.portal.Settings.Read")
-        .arg("string:org.freedesktop.appearance")
-        .arg("string:color-scheme")
+        .arg(format!("string:{namespace}"))
+        .arg(format!("string:{key}"))
         .output()
         .ok()
-        .and_then(|out| String::from_utf8(out.stdout).ok());
+        .filter(|out| out.status.success())
+        .and_then(|out| String::from_utf8(out.stdout).ok())
+}
+
+/// Reads a string setting with `gsettings`.
+fn gsettings_string(schema: &str, key: &str) -> Option<String> {
+    // outputs something like: `'Cantarell Bold 12'`
+    let stdout = Command::new("gsettings")
+        .args(["get", schema, key])
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| String::from_utf8(out.stdout).ok())?;
+    Some(stdout.trim().trim_matches('\'').to_owned())
+}
+
+/// Reads a string setting of a `gsettings` schema from the dconf database.
+fn dconf_string(schema: &str, key: &str) -> Option<String> {
+    dconf::read_string(&format!("/{}/{key}", schema.replace('.', "/")))
+}
+
+/// `GTK_THEME`, GTK's own override of the theme variant, like `Adwaita:dark`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct EnvSettings;
+
+impl SettingsProvider for EnvSettings {
+    fn prefer_dark(&self) -> Option<bool> {
+        let theme = env::var("GTK_THEME").ok()?;
+        Some(theme.ends_with(":dark"))
+    }
+}
+
+/// `org.freedesktop.portal.Settings` of the desktop portal, through `dbus-send`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PortalSettings;
+
+impl SettingsProvider for PortalSettings {
+    fn prefer_dark(&self) -> Option<bool> {
+        // outputs something like: `variant       variant          uint32 1`
+        let stdout = read_setting("org.freedesktop.appearance", "color-scheme")?;
+        match stdout.trim() {
+            s if s.ends_with("uint32 1") => Some(true),
+            s if s.ends_with("uint32 2") => Some(false),
+            // No preference, left to the other sources.
+            _ => None,
+        }
+    }
+
+    fn animations(&self) -> Option<bool> {
+        // outputs something like: `variant       variant          boolean false`
+        let stdout = read_setting("org.gnome.desktop.interface", "enable-animations")?;
+        boolean(stdout.split_whitespace().last()?)
+    }
+}
+
+/// The GNOME settings, through the `gsettings` tool.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct GsettingsSettings;
+
+impl SettingsProvider for GsettingsSettings {
+    fn prefer_dark(&self) -> Option<bool> {
+        color_scheme(&gsettings_string(INTERFACE, "color-scheme")?)
+    }
+
+    fn animations(&self) -> Option<bool> {
+        boolean(&gsettings_string(INTERFACE, "enable-animations")?)
+    }
+
+    fn titlebar_font(&self) -> Option<String> {
+        gsettings_string(WM_PREFERENCES, "titlebar-font")
+    }
+
+    fn button_layout(&self) -> Option<Vec<ButtonKind>> {
+        Some(parse_layout(&gsettings_string(
+            WM_PREFERENCES,
+            "button-layout",
+        )?))
+    }
+}
+
+/// The GNOME settings, read from the user's dconf database without any tools.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct DconfSettings;
+
+impl SettingsProvider for DconfSettings {
+    fn prefer_dark(&self) -> Option<bool> {
+        color_scheme(&dconf_string(INTERFACE, "color-scheme")?)
+    }
+
+    fn titlebar_font(&self) -> Option<String> {
+        dconf_string(WM_PREFERENCES, "titlebar-font")
+    }
+
+    fn button_layout(&self) -> Option<Vec<ButtonKind>> {
+        Some(parse_layout(&dconf_string(
+            WM_PREFERENCES,
+            "button-layout",
+        )?))
+    }
+}
+
+const INTERFACE: &str = "org.gnome.desktop.interface";
+const WM_PREFERENCES: &str = "org.gnome.desktop.wm.preferences";
+
+fn color_scheme(value: &str) -> Option<bool> {
+    match value {
+        "prefer-dark" => Some(true),
+        "prefer-light" | "default" => Some(false),
+        _ => None,
+    }
+}
+
+fn boolean(value: &str) -> Option<bool> {
+    match value {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Query system to see if dark theming should be preferred.
+pub(crate) fn prefer_dark() -> Resolved<bool> {
+    resolve(
+        "dark color scheme",
+        SYSTEM_SOURCES,
+        |p| p.prefer_dark(),
+        false,
+    )
+}
+
+/// Query system to see if animations are enabled, they are unless turned off explicitly.
+pub(crate) fn animations() -> Resolved<bool> {
+    resolve("animations", SYSTEM_SOURCES, |p| p.animations(), true)
+}
+
+pub(crate) fn animations_enabled() -> bool {
+    animations().value
+}
+
+/// Query system for the font description of window titles, like `Cantarell Bold 12`.
+#[cfg(any(feature = "ab_glyph", feature = "crossfont"))]
+pub(crate) fn titlebar_font() -> Resolved<Option<String>> {
+    resolve(
+        "titlebar font",
+        SYSTEM_SOURCES,
+        |p| p.titlebar_font().map(Some),
+        None,
+    )
+}
+
+/// Query system for the buttons of the titlebar.
+pub(crate) fn button_layout() -> Resolved<Option<Vec<ButtonKind>>> {
+    resolve(
+        "button layout",
+        SYSTEM_SOURCES,
+        |p| p.button_layout().map(Some),
+        None,
+    )
+}
+
+#[test]
+fn first_source_with_the_setting_wins() {
+    let resolved = Chain::new("test")
+        .or(SettingsSource::Env, || None)
+        .or(SettingsSource::Portal, || Some(1))
+        .or(SettingsSource::Gsettings, || unreachable!())
+        .or_default(0);
+    assert_eq!(resolved.value, 1);
+    assert_eq!(resolved.source, SettingsSource::Portal);
+
+    let resolved = Chain::new("test")
+        .or(SettingsSource::Dconf, || None)
+        .or_default(0);
+    assert_eq!(resolved.source, SettingsSource::Default);
+}
+
+#[test]
+fn providers_without_the_setting_are_skipped() {
+    struct Nothing;
+    impl SettingsProvider for Nothing {}
+    struct Dark;
+    impl SettingsProvider for Dark {
+        fn prefer_dark(&self) -> Option<bool> {
+            Some(true)
+        }
+    }
 
-    matches!(stdout, Some(s) if s.trim().ends_with("uint32 1"))
+    let sources: &[Source] = &[
+        (SettingsSource::Provider, &Nothing),
+        (SettingsSource::Env, &Dark),
+    ];
+    let resolved = resolve("test", sources, |p| p.prefer_dark(), false);
+    assert!(resolved.value);
+    assert_eq!(resolved.source, SettingsSource::Env);
+    let resolved = resolve("test", sources, |p| p.animations(), true);
+    assert_eq!(resolved.source, SettingsSource::Default);
 }
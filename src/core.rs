@@ -0,0 +1,1532 @@
+//! Drawing, layout and hit testing of the decorations, free of any Wayland types.
+//!
+//! [`AdwaitaFrame`](crate::AdwaitaFrame) is the glue between this and the compositor: it tracks
+//! the window state from sctk, hands pixels to shm buffers of its subsurfaces and turns pointer
+//! events into requests. Toolkits with their own rendering and input handling can use this module
+//! on its own, e.g. to composite the decorations into a single surface.
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use tiny_skia::{
+    BlendMode, ClipMask, Color, FillRule, Paint, Path, PathBuilder, Pixmap, PixmapMut, PixmapPaint,
+    Point, Rect, Transform,
+};
+
+use crate::{
+    buttons::{ButtonIcons, ButtonKind, Buttons, IconKey},
+    shadow::Shadow,
+    theme::{ColorMap, ColorTheme, Sizes, BORDER_SIZE, CORNER_RADIUS, HEADER_SIZE},
+    title::TitleText,
+    DecorationPart, Location, SkiaResult,
+};
+
+/// Sides of a window, e.g. the ones it is tiled on.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Edges {
+    pub top: bool,
+    pub left: bool,
+    pub right: bool,
+    pub bottom: bool,
+}
+
+impl Edges {
+    pub const NONE: Edges = Edges {
+        top: false,
+        left: false,
+        right: false,
+        bottom: false,
+    };
+
+    pub const ALL: Edges = Edges {
+        top: true,
+        left: true,
+        right: true,
+        bottom: true,
+    };
+
+    /// Whether any of the edges is set.
+    pub fn any(self) -> bool {
+        self.top || self.left || self.right || self.bottom
+    }
+}
+
+/// Rectangle in logical coordinates.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct LogicalRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl LogicalRect {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Whether the point lies within the rectangle, its right and bottom edges excluded.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        let (left, top) = (f64::from(self.x), f64::from(self.y));
+        (left..left + f64::from(self.width)).contains(&x)
+            && (top..top + f64::from(self.height)).contains(&y)
+    }
+}
+
+/// Smallest window content the decorations are laid out for, in logical pixels.
+pub const MIN_CONTENT_SIZE: (u32, u32) = (1, 1);
+
+/// Largest window content the decorations are laid out for, in logical pixels.
+///
+/// At scale 1 every buffer of the decorations, like a row of `4 * (width + 2 * border)` bytes,
+/// stays well within the `i32` size of a shm pool. Buffers which get too large at higher scales
+/// are reported as [`DecorationError::BufferTooLarge`](crate::DecorationError::BufferTooLarge).
+pub const MAX_CONTENT_SIZE: (u32, u32) = (1 << 15, 1 << 15);
+
+/// Clamps a content size between [`MIN_CONTENT_SIZE`] and [`MAX_CONTENT_SIZE`].
+///
+/// Sizes beyond `i32::MAX`, like negative sizes cast to `u32`, count as zero: surface sizes are
+/// `i32` in the protocol.
+pub fn sanitize_size((width, height): (u32, u32)) -> (u32, u32) {
+    let sanitize = |value: u32, min: u32, max: u32| {
+        if value > i32::MAX as u32 {
+            min
+        } else {
+            value.clamp(min, max)
+        }
+    };
+    (
+        sanitize(width, MIN_CONTENT_SIZE.0, MAX_CONTENT_SIZE.0),
+        sanitize(height, MIN_CONTENT_SIZE.1, MAX_CONTENT_SIZE.1),
+    )
+}
+
+/// Edges without a border: all of them while maximized, otherwise the tiled ones. Tiling both
+/// edges of an axis is how a window gets maximized only horizontally or vertically.
+pub(crate) fn hidden_borders(maximized: bool, tiled: Edges) -> Edges {
+    if maximized {
+        Edges::ALL
+    } else {
+        tiled
+    }
+}
+
+/// What the header shows.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum HeaderMode {
+    /// A headerbar above the window content, with the title and buttons.
+    #[default]
+    Full,
+    /// Only the buttons, floating over the top right corner of the window content, for apps
+    /// drawing their content edge to edge.
+    ButtonsOnly,
+}
+
+impl HeaderMode {
+    /// `sizes` as far as the window geometry is concerned, floating buttons add no header
+    /// above the content.
+    pub(crate) fn geometry(self, sizes: Sizes) -> Sizes {
+        match self {
+            HeaderMode::Full => sizes,
+            HeaderMode::ButtonsOnly => Sizes { header: 0, ..sizes },
+        }
+    }
+}
+
+/// Everything the look of the decorations depends on.
+#[derive(Debug, Clone)]
+pub struct RenderState {
+    /// Size of the window content in logical pixels, without decorations, as clamped by
+    /// [`sanitize_size`].
+    pub size: (u32, u32),
+    pub scale: u32,
+    pub theme: ColorTheme,
+    /// Whether the window has keyboard focus.
+    pub active: bool,
+    pub resizable: bool,
+    pub maximized: bool,
+    /// Edges touching a neighbouring window or the screen edge, which have neither a border nor
+    /// rounded corners.
+    pub tiled: Edges,
+    /// Button under the pointer.
+    pub hovered: Option<ButtonKind>,
+    pub title: Option<String>,
+    pub anti_alias: bool,
+    /// Whether the shadow of floating, focused windows is drawn.
+    pub shadows: bool,
+    /// Titlebar buttons, from right to left.
+    pub button_layout: Vec<ButtonKind>,
+    /// Radius of the top corners of floating windows, in logical pixels.
+    pub corner_radius: f32,
+    /// Title font, like `Cantarell Bold 11`, instead of the system titlebar font.
+    pub title_font: Option<String>,
+    /// Height of the header in logical pixels.
+    pub header_height: u32,
+    /// Width of the borders in logical pixels.
+    pub border_size: u32,
+    pub header_mode: HeaderMode,
+    /// Part of the headerbar left to the app, relative to the headerbar.
+    pub reserved_area: Option<LogicalRect>,
+}
+
+impl RenderState {
+    /// An active, resizable window with the light theme.
+    pub fn new(size: (u32, u32), scale: u32) -> Self {
+        Self {
+            size,
+            scale,
+            theme: ColorTheme::light(),
+            active: true,
+            resizable: true,
+            maximized: false,
+            tiled: Edges::NONE,
+            hovered: None,
+            title: None,
+            anti_alias: true,
+            shadows: true,
+            button_layout: ButtonKind::ALL.to_vec(),
+            corner_radius: CORNER_RADIUS,
+            title_font: None,
+            header_height: HEADER_SIZE,
+            border_size: BORDER_SIZE,
+            header_mode: HeaderMode::Full,
+            reserved_area: None,
+        }
+    }
+
+    pub(crate) fn hidden_borders(&self) -> Edges {
+        hidden_borders(self.maximized, self.tiled)
+    }
+
+    pub(crate) fn sizes(&self) -> Sizes {
+        Sizes {
+            header: self.header_height,
+            border: self.border_size,
+        }
+    }
+
+    /// Buttons laid out like the frame would.
+    pub(crate) fn buttons(&self) -> Buttons {
+        let mut buttons = Buttons::default();
+        buttons.set_layout(self.button_layout.clone());
+        buttons.set_sizes(self.sizes());
+        buttons.update_scale(self.scale);
+        buttons.arrange(self.size.0 + 2 * self.border_size);
+        buttons
+    }
+}
+
+/// Renders the header and borders of a window into a pixmap, the way the frame would draw them.
+///
+/// The pixmap covers the decorations and the window content, which is left transparent. The
+/// content starts at `(border_size, border_size + header_height) * scale`, or at
+/// `(border_size, border_size) * scale` below floating buttons.
+pub fn render_decorations(state: &RenderState) -> Option<Pixmap> {
+    let (width, height) = state.size;
+    let scale = state.scale;
+    let Sizes { header, border } = state.header_mode.geometry(state.sizes());
+
+    let mut pixmap = Pixmap::new(
+        (width + 2 * border).checked_mul(scale)?,
+        (header + height + 2 * border).checked_mul(scale)?,
+    )?;
+    draw_decorations(state, &mut pixmap.as_mut(), (0, 0))?;
+
+    Some(pixmap)
+}
+
+/// Draws the header and borders over a canvas of the embedder, for toolkits compositing
+/// everything into a single surface instead of using the frame's subsurfaces.
+///
+/// The decorations are laid out like in [`render_decorations`], with their top left corner at
+/// `origin` in pixels of `pixmap`. The window content area is left untouched, besides floating
+/// buttons, and [`hit_test`] tells what the pointer is over.
+pub fn draw_decorations(
+    state: &RenderState,
+    pixmap: &mut PixmapMut,
+    origin: (i32, i32),
+) -> Option<()> {
+    let (width, height) = state.size;
+    let scale = state.scale;
+    let colors = state.theme.colors(state.active);
+    let sizes = state.sizes();
+    let geometry = state.header_mode.geometry(sizes);
+    let buttons = state.buttons();
+
+    let mut title_text = TitleText::new(colors.font_color, state.title_font.as_deref());
+    if let (Some(title_text), Some(title)) = (title_text.as_mut(), state.title.as_ref()) {
+        title_text.update_scale(scale);
+        title_text.update_title(title.as_str());
+    }
+
+    let mut hovered = [false; 3];
+    if let Some(kind) = state.hovered {
+        hovered[kind as usize] = true;
+    }
+
+    let shadow_at = |part: DecorationPart| {
+        let origin = part_origin(part, geometry, state.size);
+        let floating = !state.maximized && !state.tiled.any();
+        (state.shadows && floating && state.active).then_some(Shadow {
+            origin: (origin.0 as f32, origin.1 as f32),
+            window: state.size,
+            scale,
+            corner_radius: state.corner_radius,
+            sizes: geometry,
+        })
+    };
+
+    let (header_width, header_height) = buttons.scaled_size();
+    let mut header = Pixmap::new(header_width, header_height + sizes.border * scale)?;
+    if let Some(shadow) = shadow_at(DecorationPart::Header) {
+        draw_header_shadow(&mut header.as_mut(), &shadow, state.header_mode);
+    }
+    draw_headerbar(
+        &mut header.as_mut(),
+        title_text.as_ref().and_then(|t| t.pixmap()),
+        scale as f32,
+        state.resizable,
+        state.maximized,
+        state.tiled,
+        state.corner_radius,
+        state.active,
+        state.header_mode,
+        colors,
+        &buttons,
+        &ButtonIcons::default(),
+        hovered,
+        state.anti_alias,
+        &mut HeaderScratch::default(),
+    )?;
+    if let Some(area) = state.reserved_area {
+        clear_reserved_area(&mut header.as_mut(), area, width, sizes, scale);
+    }
+    blit(pixmap, &header, origin, 0, 0);
+
+    let hidden = state.hidden_borders();
+    let border_paint = colors.border_paint();
+    let border = sizes.border * scale;
+    let top = (geometry.header + sizes.border) * scale;
+
+    if !hidden.bottom {
+        let mut bottom = Pixmap::new((width + 2 * sizes.border) * scale, border)?;
+        let (w, h) = (bottom.width(), bottom.height());
+        let shadow = shadow_at(DecorationPart::Bottom);
+        draw_bottom_border(bottom.data_mut(), w, h, &border_paint, shadow)?;
+        blit(pixmap, &bottom, origin, 0, top + height * scale);
+    }
+
+    // Side borders of a window without height are empty.
+    if height == 0 {
+        return Some(());
+    }
+
+    if !hidden.left {
+        let mut left = Pixmap::new(border, height * scale)?;
+        let (w, h) = (left.width(), left.height());
+        let shadow = shadow_at(DecorationPart::Left);
+        draw_left_border(left.data_mut(), w, h, &border_paint, shadow)?;
+        blit(pixmap, &left, origin, 0, top);
+    }
+
+    if !hidden.right {
+        let mut right = Pixmap::new(border, height * scale)?;
+        let (w, h) = (right.width(), right.height());
+        let shadow = shadow_at(DecorationPart::Right);
+        draw_right_border(right.data_mut(), w, h, &border_paint, shadow)?;
+        blit(pixmap, &right, origin, border + width * scale, top);
+    }
+
+    Some(())
+}
+
+/// Edge or corner of the window a resize starts from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ResizeEdge {
+    Top,
+    TopRight,
+    Right,
+    BottomRight,
+    Bottom,
+    BottomLeft,
+    Left,
+    TopLeft,
+}
+
+/// What the pointer is over, see [`hit_test`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DecorationHit {
+    /// Neither the decorations nor a resize margin, e.g. the window content.
+    None,
+    /// The titlebar, dragging it moves the window.
+    Header,
+    Button(ButtonKind),
+    /// A resize margin of a resizable window.
+    Resize(ResizeEdge),
+}
+
+impl DecorationHit {
+    /// What a left click here asks of the window, the same as on the frame's own decorations.
+    ///
+    /// `double_click` is whether the click completes a double click. Buttons act once released,
+    /// see [`ButtonKind::request`].
+    pub fn press_request(self, double_click: bool, maximized: bool) -> Option<PointerRequest> {
+        match self {
+            DecorationHit::Header if double_click && maximized => Some(PointerRequest::UnMaximize),
+            DecorationHit::Header if double_click => Some(PointerRequest::Maximize),
+            DecorationHit::Header => Some(PointerRequest::Move),
+            DecorationHit::Resize(edge) => Some(PointerRequest::Resize(edge)),
+            DecorationHit::Button(_) | DecorationHit::None => None,
+        }
+    }
+}
+
+impl ButtonKind {
+    /// What releasing the left button over this button asks of the window, after it was pressed
+    /// on the same button.
+    pub fn request(self, maximized: bool) -> PointerRequest {
+        match self {
+            ButtonKind::Close => PointerRequest::Close,
+            ButtonKind::Maximize if maximized => PointerRequest::UnMaximize,
+            ButtonKind::Maximize => PointerRequest::Maximize,
+            ButtonKind::Minimize => PointerRequest::Minimize,
+        }
+    }
+}
+
+/// A request of the decorations to the window, like sctk's `FrameRequest` without the seat.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PointerRequest {
+    Move,
+    Resize(ResizeEdge),
+    /// The window menu, at a position relative to the window content.
+    ShowMenu {
+        x: i32,
+        y: i32,
+    },
+    Maximize,
+    UnMaximize,
+    Minimize,
+    Close,
+    /// The decorations need to be redrawn.
+    Refresh,
+}
+
+/// Part of the decorations drawn by [`draw_decorations`] at `(x, y)`, in logical pixels from
+/// their top left corner.
+///
+/// Follows the frame's own pointer handling: the top border of a non-resizable window moves it,
+/// and hidden borders, like those of maximized windows, aren't there to resize from.
+pub fn hit_test(state: &RenderState, x: f64, y: f64) -> DecorationHit {
+    let buttons = state.buttons();
+    let (surface, x, y) = match surface_at(state, &buttons, x, y) {
+        Some((Location::None, ..)) | None => return DecorationHit::None,
+        Some(surface) => surface,
+    };
+
+    let Sizes { border, .. } = state.header_mode.geometry(state.sizes());
+    let (width, _) = state.size;
+    let hidden = state.hidden_borders();
+    let location = precise_location(&buttons, surface, width, hidden, x, y);
+    let location = reserve(location, state.reserved_area, border, x, y);
+    location_hit(location, state.resizable)
+}
+
+/// What the frame does at `location`, as reported by [`hit_test`].
+pub(crate) fn location_hit(location: Location, resizable: bool) -> DecorationHit {
+    let edge = match location {
+        Location::None | Location::Reserved => return DecorationHit::None,
+        Location::Head | Location::Drag => return DecorationHit::Header,
+        Location::Button(kind) => return DecorationHit::Button(kind),
+        Location::Top => ResizeEdge::Top,
+        Location::TopRight => ResizeEdge::TopRight,
+        Location::Right => ResizeEdge::Right,
+        Location::BottomRight => ResizeEdge::BottomRight,
+        Location::Bottom => ResizeEdge::Bottom,
+        Location::BottomLeft => ResizeEdge::BottomLeft,
+        Location::Left => ResizeEdge::Left,
+        Location::TopLeft => ResizeEdge::TopLeft,
+    };
+
+    if resizable {
+        DecorationHit::Resize(edge)
+    } else if matches!(
+        edge,
+        ResizeEdge::Top | ResizeEdge::TopLeft | ResizeEdge::TopRight
+    ) {
+        DecorationHit::Header
+    } else {
+        DecorationHit::None
+    }
+}
+
+/// The surface the frame would report the pointer over at `(x, y)`, with coordinates local to
+/// it, as in [`hit_test`]. [`Location::None`] stands for the window content.
+pub(crate) fn surface_at(
+    state: &RenderState,
+    buttons: &Buttons,
+    x: f64,
+    y: f64,
+) -> Option<(Location, f64, f64)> {
+    let (width, height) = state.size;
+    let Sizes { header, border } = state.header_mode.geometry(state.sizes());
+    let full_width = f64::from(width + 2 * border);
+    let header_bottom = f64::from(border + header);
+    let content_bottom = header_bottom + f64::from(height);
+    if x < 0.0 || y < 0.0 || x >= full_width || y >= content_bottom + f64::from(border) {
+        return None;
+    }
+
+    Some(if y < header_bottom || in_button_cluster(buttons, x, y) {
+        (Location::Head, x, y)
+    } else if y >= content_bottom {
+        (Location::Bottom, x, y - content_bottom)
+    } else if x < f64::from(border) {
+        (Location::Left, x, y - header_bottom)
+    } else if x >= f64::from(border + width) {
+        (
+            Location::Right,
+            x - f64::from(border + width),
+            y - header_bottom,
+        )
+    } else {
+        (Location::None, x - f64::from(border), y - header_bottom)
+    })
+}
+
+/// Whether `(x, y)` on the header surface is around the buttons, where floating buttons take
+/// input.
+pub(crate) fn in_button_cluster(buttons: &Buttons, x: f64, y: f64) -> bool {
+    let (cx, cy, cw, ch) = buttons.cluster();
+    x >= f64::from(cx) && x < f64::from(cx + cw) && y >= f64::from(cy) && y < f64::from(cy + ch)
+}
+
+fn blit(pixmap: &mut PixmapMut, part: &Pixmap, origin: (i32, i32), x: u32, y: u32) {
+    pixmap.draw_pixmap(
+        origin.0 + x as i32,
+        origin.1 + y as i32,
+        part.as_ref(),
+        &PixmapPaint::default(),
+        Transform::identity(),
+        None,
+    );
+}
+
+pub(crate) fn precise_location(
+    buttons: &Buttons,
+    old: Location,
+    width: u32,
+    hidden: Edges,
+    x: f64,
+    y: f64,
+) -> Location {
+    let border = f64::from(buttons.sizes().border);
+
+    let location = match old {
+        Location::Head
+        | Location::Button(_)
+        | Location::Reserved
+        | Location::Top
+        | Location::TopLeft
+        | Location::TopRight => match buttons.find_button(x, y) {
+            Location::Head => {
+                if y <= border {
+                    if x <= border {
+                        Location::TopLeft
+                    } else if x >= f64::from(width) + border {
+                        Location::TopRight
+                    } else {
+                        Location::Top
+                    }
+                } else if x < border {
+                    Location::TopLeft
+                } else if x > f64::from(width) {
+                    Location::TopRight
+                } else {
+                    Location::Head
+                }
+            }
+            other => other,
+        },
+
+        Location::Bottom | Location::BottomLeft | Location::BottomRight => {
+            if x <= border {
+                Location::BottomLeft
+            } else if x >= f64::from(width) + border {
+                Location::BottomRight
+            } else {
+                Location::Bottom
+            }
+        }
+
+        other => other,
+    };
+
+    without_hidden_edges(location, hidden)
+}
+
+/// Hidden borders (e.g. while maximized) leave nothing to resize from, the header surface
+/// above them is left to the titlebar.
+fn without_hidden_edges(location: Location, hidden: Edges) -> Location {
+    let (top, left, right, bottom) = match location {
+        Location::Top => (true, false, false, false),
+        Location::TopLeft => (true, true, false, false),
+        Location::TopRight => (true, false, true, false),
+        Location::Left => (false, true, false, false),
+        Location::Right => (false, false, true, false),
+        Location::BottomLeft => (false, true, false, true),
+        Location::BottomRight => (false, false, true, true),
+        Location::Bottom => (false, false, false, true),
+        other => return other,
+    };
+    let in_header = top;
+
+    let top = top && !hidden.top;
+    let left = left && !hidden.left;
+    let right = right && !hidden.right;
+    let bottom = bottom && !hidden.bottom;
+    match (top, left, right, bottom) {
+        (true, true, _, _) => Location::TopLeft,
+        (true, _, true, _) => Location::TopRight,
+        (true, ..) => Location::Top,
+        (_, true, _, true) => Location::BottomLeft,
+        (_, _, true, true) => Location::BottomRight,
+        (.., true) => Location::Bottom,
+        (_, true, ..) => Location::Left,
+        (_, _, true, _) => Location::Right,
+        _ if in_header => Location::Head,
+        _ => Location::None,
+    }
+}
+
+/// Allocations of the header drawing, reused so steady redraws don't allocate.
+#[derive(Debug, Default)]
+pub(crate) struct HeaderScratch {
+    /// Emptied path of the previous draw.
+    path: PathBuilder,
+    /// Clip of the title, resized in place.
+    clip: ClipMask,
+}
+
+/// Position of a decoration surface relative to the window content of logical `size`.
+pub(crate) fn part_origin(part: DecorationPart, sizes: Sizes, size: (u32, u32)) -> (i32, i32) {
+    let border = sizes.border as i32;
+    match part {
+        DecorationPart::Header => (-border, -(sizes.header as i32 + border)),
+        DecorationPart::Left => (-border, 0),
+        DecorationPart::Right => (size.0 as i32, 0),
+        DecorationPart::Bottom => (-border, size.1 as i32),
+    }
+}
+
+/// Width of the window outline.
+///
+/// Border geometry is computed in buffer pixels, so this is a single device pixel at every
+/// scale rather than one logical pixel.
+const OUTLINE_WIDTH: f32 = 1.0;
+
+/// Paint restoring the headerbar background behind a button before it gets redrawn, floating
+/// buttons have none.
+///
+/// Replaces the pixels instead of blending, a translucent headerbar would get darker with
+/// every hover change otherwise.
+pub(crate) fn button_clear_paint(colors: &ColorMap, mode: HeaderMode) -> Paint<'_> {
+    let mut paint = colors.headerbar_paint();
+    if mode == HeaderMode::ButtonsOnly {
+        paint.set_color(Color::TRANSPARENT);
+    }
+    paint.anti_alias = false;
+    paint.blend_mode = BlendMode::Source;
+    paint
+}
+
+/// Draws the shadow of the header surface. Below floating buttons the side borders hold the
+/// shadow, only the top margin is left to the header.
+pub(crate) fn draw_header_shadow(pixmap: &mut PixmapMut, shadow: &Shadow, mode: HeaderMode) {
+    shadow.draw(pixmap);
+    if mode == HeaderMode::ButtonsOnly {
+        let top = (shadow.sizes.border * shadow.scale) as f32;
+        let height = pixmap.height() as f32 - top;
+        if let Some(rect) = Rect::from_xywh(0.0, top, pixmap.width() as f32, height) {
+            let mut paint = Paint::default();
+            paint.set_color(Color::TRANSPARENT);
+            paint.blend_mode = BlendMode::Source;
+            pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+        }
+    }
+}
+
+/// Turns the headerbar within the area reserved for the app into [`Location::Reserved`], `x`
+/// and `y` are relative to the header surface, including the borders.
+pub(crate) fn reserve(
+    location: Location,
+    area: Option<LogicalRect>,
+    border: u32,
+    x: f64,
+    y: f64,
+) -> Location {
+    let border = f64::from(border);
+    match area {
+        Some(area) if location == Location::Head && area.contains(x - border, y - border) => {
+            Location::Reserved
+        }
+        _ => location,
+    }
+}
+
+/// Clears the part of the headerbar reserved for the app, `area` is relative to the headerbar
+/// of a window `width` wide.
+pub(crate) fn clear_reserved_area(
+    pixmap: &mut PixmapMut,
+    area: LogicalRect,
+    width: u32,
+    sizes: Sizes,
+    scale: u32,
+) {
+    let left = area.x.max(0) as f32;
+    let top = area.y.max(0) as f32;
+    let right = (area.x + area.width as i32).min(width as i32) as f32;
+    let bottom = (area.y + area.height as i32).min(sizes.header as i32) as f32;
+    let (border, scale) = (sizes.border as f32, scale as f32);
+    if let Some(rect) = Rect::from_ltrb(
+        (border + left) * scale,
+        (border + top) * scale,
+        (border + right) * scale,
+        (border + bottom) * scale,
+    ) {
+        let mut paint = Paint::default();
+        paint.set_color(Color::TRANSPARENT);
+        paint.blend_mode = BlendMode::Source;
+        pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+    }
+}
+
+pub(crate) fn draw_bottom_border(
+    canvas: &mut [u8],
+    width: u32,
+    height: u32,
+    border_paint: &Paint,
+    shadow: Option<Shadow>,
+) -> SkiaResult {
+    let mut pixmap = PixmapMut::from_bytes(canvas, width, height)?;
+    pixmap.fill(Color::TRANSPARENT);
+    if let Some(shadow) = shadow {
+        shadow.draw(&mut pixmap);
+    }
+
+    // The bottom border is as high as the side borders are wide.
+    let size = OUTLINE_WIDTH;
+    let x = height as f32 - OUTLINE_WIDTH;
+    pixmap.fill_rect(
+        Rect::from_xywh(x, 0.0, width as f32 - height as f32 * 2.0 + 2.0, size)?,
+        border_paint,
+        Transform::identity(),
+        None,
+    );
+
+    Some(())
+}
+
+pub(crate) fn draw_left_border(
+    canvas: &mut [u8],
+    width: u32,
+    height: u32,
+    border_paint: &Paint,
+    shadow: Option<Shadow>,
+) -> SkiaResult {
+    let mut pixmap = PixmapMut::from_bytes(canvas, width, height)?;
+    pixmap.fill(Color::TRANSPARENT);
+    if let Some(shadow) = shadow {
+        shadow.draw(&mut pixmap);
+    }
+
+    let size = OUTLINE_WIDTH;
+    pixmap.fill_rect(
+        Rect::from_xywh(width as f32 - size, 0.0, width as f32, height as f32)?,
+        border_paint,
+        Transform::identity(),
+        None,
+    );
+
+    Some(())
+}
+
+pub(crate) fn draw_right_border(
+    canvas: &mut [u8],
+    width: u32,
+    height: u32,
+    border_paint: &Paint,
+    shadow: Option<Shadow>,
+) -> SkiaResult {
+    let mut pixmap = PixmapMut::from_bytes(canvas, width, height)?;
+    pixmap.fill(Color::TRANSPARENT);
+    if let Some(shadow) = shadow {
+        shadow.draw(&mut pixmap);
+    }
+
+    let size = OUTLINE_WIDTH;
+    pixmap.fill_rect(
+        Rect::from_xywh(0.0, 0.0, size, height as f32)?,
+        border_paint,
+        Transform::identity(),
+        None,
+    );
+
+    Some(())
+}
+
+/// Draws the header, details which don't fit narrow windows are left out step by step:
+///
+/// - buttons which don't fit in front of the left border are dropped, minimize first, then
+///   maximize and close, see [`Buttons::fits`],
+/// - corners are clamped to half the header width and height, so they meet rather than cross,
+///
+/// so down to a window width of zero a solid header is left. Floating buttons are drawn on their
+/// own.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn draw_headerbar(
+    pixmap: &mut PixmapMut,
+    text_pixmap: Option<&Pixmap>,
+    scale: f32,
+    maximizable: bool,
+    is_maximized: bool,
+    tiled: Edges,
+    corner_radius: f32,
+    active: bool,
+    mode: HeaderMode,
+    colors: &ColorMap,
+    buttons: &Buttons,
+    icons: &ButtonIcons,
+    hovered: [bool; 3],
+    anti_alias: bool,
+    scratch: &mut HeaderScratch,
+) -> SkiaResult {
+    let border_size = buttons.sizes().border as f32 * scale;
+
+    let margin_h = border_size;
+    let margin_v = border_size;
+
+    if mode == HeaderMode::Full {
+        draw_headerbar_bg(
+            pixmap,
+            scale,
+            margin_h,
+            margin_v,
+            colors,
+            is_maximized,
+            tiled,
+            corner_radius,
+            anti_alias,
+            scratch,
+        )?;
+    }
+
+    if let Some(text_pixmap) = text_pixmap.filter(|_| mode == HeaderMode::Full) {
+        let canvas_w = pixmap.width() as f32;
+        let canvas_h = pixmap.height() as f32;
+
+        let header_w = canvas_w - margin_h * 2.0;
+        let header_h = canvas_h - margin_v;
+
+        let text_w = text_pixmap.width() as f32;
+        let text_h = text_pixmap.height() as f32;
+
+        let x = header_w / 2.0 - text_w / 2.0;
+        let y = header_h / 2.0 - text_h / 2.0;
+
+        let x = margin_h + x;
+        let y = margin_v + y;
+
+        let (x, y) = if x + text_w < buttons.left_edge() - 10.0 {
+            (x, y)
+        } else {
+            let y = header_h / 2.0 - text_h / 2.0;
+
+            let x = buttons.left_edge() - text_w - 10.0;
+            let y = margin_v + y;
+            (x, y)
+        };
+
+        let x = x.max(margin_h + 5.0);
+
+        if let Some(clip) = Rect::from_xywh(0.0, 0.0, buttons.left_edge() - 10.0, canvas_h) {
+            let mut pb = std::mem::take(&mut scratch.path);
+            pb.push_rect(clip.x(), clip.y(), clip.width(), clip.height());
+            if let Some(path) = pb.finish() {
+                scratch.clip.set_path(
+                    canvas_w as u32,
+                    canvas_h as u32,
+                    &path,
+                    FillRule::Winding,
+                    false,
+                );
+                pixmap.draw_pixmap(
+                    x as i32,
+                    y as i32,
+                    text_pixmap.as_ref(),
+                    &PixmapPaint::default(),
+                    Transform::identity(),
+                    Some(&scratch.clip),
+                );
+                scratch.path = path.clear();
+            }
+        }
+    }
+
+    for kind in ButtonKind::ALL {
+        draw_button(
+            pixmap,
+            icons,
+            kind,
+            scale as u32,
+            colors,
+            active,
+            buttons,
+            hovered,
+            maximizable,
+            is_maximized,
+            anti_alias,
+        );
+    }
+
+    Some(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn draw_button(
+    pixmap: &mut PixmapMut,
+    icons: &ButtonIcons,
+    kind: ButtonKind,
+    scale: u32,
+    colors: &ColorMap,
+    active: bool,
+    buttons: &Buttons,
+    hovered: [bool; 3],
+    maximizable: bool,
+    is_maximized: bool,
+    anti_alias: bool,
+) {
+    if !buttons.fits(kind) {
+        return;
+    }
+    let button = buttons.get(kind);
+
+    let key = IconKey {
+        kind,
+        scale,
+        active,
+        hovered: hovered[kind as usize],
+        maximizable,
+        maximized: is_maximized,
+        anti_alias,
+    };
+    icons.draw(pixmap, button, key, colors);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_headerbar_bg(
+    pixmap: &mut PixmapMut,
+    scale: f32,
+    margin_h: f32,
+    margin_v: f32,
+    colors: &ColorMap,
+    is_maximized: bool,
+    tiled: Edges,
+    corner_radius: f32,
+    anti_alias: bool,
+    scratch: &mut HeaderScratch,
+) -> SkiaResult {
+    let w = pixmap.width() as f32;
+    let h = pixmap.height() as f32;
+
+    // Cover the outline of the side borders.
+    let margin_h = margin_h - OUTLINE_WIDTH;
+    let w = w - margin_h * 2.0;
+
+    let radius = corner_radius * scale;
+    // Corners along a tiled edge touch its neighbour.
+    let rounded = !is_maximized && !tiled.top;
+    let corner = |tiled: bool| if rounded && !tiled { radius } else { 0.0 };
+    let radii = (corner(tiled.left), corner(tiled.right));
+
+    let pb = std::mem::take(&mut scratch.path);
+    let Some(bg) = rounded_headerbar_shape(pb, margin_h, margin_v, w, h - margin_v, radii) else {
+        // The builder is gone with the failed path, leave a fresh one for the next frame.
+        scratch.path = PathBuilder::new();
+        return None;
+    };
+
+    let mut paint = colors.headerbar_paint();
+    paint.anti_alias = anti_alias;
+    pixmap.fill_path(&bg, &paint, FillRule::Winding, Transform::identity(), None);
+    scratch.path = bg.clear();
+
+    pixmap.fill_rect(
+        Rect::from_xywh(margin_h, h - OUTLINE_WIDTH, w, OUTLINE_WIDTH)?,
+        &colors.border_paint(),
+        Transform::identity(),
+        None,
+    );
+
+    Some(())
+}
+
+fn rounded_headerbar_shape(
+    mut pb: PathBuilder,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    (left, right): (f32, f32),
+) -> Option<Path> {
+    // Corners larger than half of the header would cross each other, e.g. on small windows.
+    let max = width.min(height).max(0.0) / 2.0;
+    let (left, right) = (left.min(max), right.min(max));
+
+    let mut cursor = Point::from_xy(x, y);
+
+    // !!!
+    // This code is heavily "inspired" by https://gitlab.com/snakedye/snui/
+    // So technically it should be licensed under MPL-2.0, sorry about that 🥺 👉👈
+    // !!!
+
+    // Positioning the cursor
+    cursor.y += left;
+    pb.move_to(cursor.x, cursor.y);
+
+    // Drawing the outline
+    pb.cubic_to(
+        cursor.x,
+        cursor.y,
+        cursor.x,
+        cursor.y - FRAC_1_SQRT_2 * left,
+        {
+            cursor.x += left;
+            cursor.x
+        },
+        {
+            cursor.y -= left;
+            cursor.y
+        },
+    );
+    pb.line_to(
+        {
+            cursor.x = x + width - right;
+            cursor.x
+        },
+        cursor.y,
+    );
+    pb.cubic_to(
+        cursor.x,
+        cursor.y,
+        cursor.x + FRAC_1_SQRT_2 * right,
+        cursor.y,
+        {
+            cursor.x += right;
+            cursor.x
+        },
+        {
+            cursor.y += right;
+            cursor.y
+        },
+    );
+    pb.line_to(cursor.x, {
+        cursor.y = y + height;
+        cursor.y
+    });
+    pb.line_to(
+        {
+            cursor.x = x;
+            cursor.x
+        },
+        cursor.y,
+    );
+
+    pb.close();
+
+    pb.finish()
+}
+
+#[test]
+fn rect_excludes_right_and_bottom_edges() {
+    let rect = LogicalRect::new(-10, 0, 20, 30);
+    assert!(rect.contains(-10.0, 0.0));
+    assert!(rect.contains(9.5, 29.5));
+    assert!(!rect.contains(10.0, 15.0));
+    assert!(!rect.contains(0.0, 30.0));
+    assert!(!LogicalRect::new(0, 0, 0, 10).contains(0.0, 5.0));
+}
+
+#[test]
+fn rendered_size() {
+    let pixmap = render_decorations(&RenderState::new((200, 100), 2)).unwrap();
+    assert_eq!(pixmap.width(), (200 + 2 * BORDER_SIZE) * 2);
+    assert_eq!(pixmap.height(), (100 + HEADER_SIZE + 2 * BORDER_SIZE) * 2);
+}
+
+#[test]
+fn custom_sizes() {
+    let state = RenderState {
+        header_height: 48,
+        border_size: 4,
+        ..RenderState::new((200, 100), 1)
+    };
+    let pixmap = render_decorations(&state).unwrap();
+    assert_eq!(pixmap.width(), 200 + 2 * 4);
+    assert_eq!(pixmap.height(), 100 + 48 + 2 * 4);
+
+    // The content starts below the header, the buttons are centered in it.
+    assert_eq!(pixmap.pixel(24, 4 + 47).unwrap().alpha(), 255);
+    assert_eq!(pixmap.pixel(24, 4 + 48).unwrap().alpha(), 0);
+    let close = state.buttons().close;
+    assert_eq!(close.center_y(), 4.0 + 24.0);
+    assert_eq!(
+        hit_test(&state, 2.0, 100.0),
+        DecorationHit::Resize(ResizeEdge::Left)
+    );
+    assert_eq!(hit_test(&state, 6.0, 100.0), DecorationHit::None);
+}
+
+#[test]
+fn content_is_transparent() {
+    let pixmap = render_decorations(&RenderState::new((200, 100), 1)).unwrap();
+    let header = pixmap.pixel(BORDER_SIZE + 20, BORDER_SIZE + 20).unwrap();
+    let content = pixmap
+        .pixel(BORDER_SIZE + 20, BORDER_SIZE + HEADER_SIZE + 20)
+        .unwrap();
+    assert_eq!(header.alpha(), 255);
+    assert_eq!(content.alpha(), 0);
+}
+
+#[test]
+fn translucent_header_is_premultiplied() {
+    let mut state = RenderState::new((200, 100), 1);
+    state.theme.active.headerbar = tiny_skia::Color::from_rgba8(200, 100, 0, 128);
+    let pixmap = render_decorations(&state).unwrap();
+
+    let header = pixmap.pixel(BORDER_SIZE + 20, BORDER_SIZE + 20).unwrap();
+    assert_eq!(header.alpha(), 128);
+    assert_eq!(header.red(), 100);
+    assert_eq!(header.green(), 50);
+    assert_eq!(header.blue(), 0);
+}
+
+#[test]
+fn outline_is_one_device_pixel() {
+    for scale in 1..=3 {
+        let state = RenderState {
+            shadows: false,
+            ..RenderState::new((200, 100), scale)
+        };
+        let pixmap = render_decorations(&state).unwrap();
+        let border_color = state.theme.active.border_color.premultiply().to_color_u8();
+
+        // Left of the content and below it, halfway along the window.
+        let left = BORDER_SIZE * scale;
+        let y = (BORDER_SIZE + HEADER_SIZE + 50) * scale;
+        assert_eq!(pixmap.pixel(left - 1, y), Some(border_color));
+        assert_eq!(pixmap.pixel(left - 2, y).unwrap().alpha(), 0);
+        assert_eq!(pixmap.pixel(left, y).unwrap().alpha(), 0);
+
+        let bottom = (BORDER_SIZE + HEADER_SIZE + 100) * scale;
+        let x = (BORDER_SIZE + 100) * scale;
+        assert_eq!(pixmap.pixel(x, bottom), Some(border_color));
+        assert_eq!(pixmap.pixel(x, bottom + 1).unwrap().alpha(), 0);
+        assert_eq!(pixmap.pixel(x, bottom - 1).unwrap().alpha(), 0);
+    }
+}
+
+#[test]
+fn aliased_rendering_has_no_partial_coverage() {
+    let state = RenderState {
+        anti_alias: false,
+        shadows: false,
+        ..RenderState::new((200, 100), 1)
+    };
+    let pixmap = render_decorations(&state).unwrap();
+    let header = (BORDER_SIZE + HEADER_SIZE) * pixmap.width();
+    assert!(pixmap.pixels()[..header as usize]
+        .iter()
+        .all(|p| p.alpha() == 0 || p.alpha() == 255));
+}
+
+#[test]
+fn shadow_only_around_focused_windows() {
+    let below_content = |state: RenderState| {
+        let pixmap = render_decorations(&state).unwrap();
+        let y = BORDER_SIZE + HEADER_SIZE + 100 + 2;
+        pixmap.pixel(BORDER_SIZE + 100, y).unwrap().alpha()
+    };
+
+    let state = RenderState::new((200, 100), 1);
+    assert!(below_content(state.clone()) > 0);
+    assert_eq!(
+        below_content(RenderState {
+            active: false,
+            ..state.clone()
+        }),
+        0
+    );
+    assert_eq!(
+        below_content(RenderState {
+            tiled: Edges {
+                left: true,
+                ..Edges::NONE
+            },
+            ..state
+        }),
+        0
+    );
+}
+
+#[test]
+fn tiny_windows_are_drawn() {
+    for scale in 1..=3 {
+        for size in [(0, 0), (1, 1), (3, 0), (0, 3), (15, 2)] {
+            let state = RenderState::new(size, scale);
+            assert!(render_decorations(&state).is_some(), "{size:?} at {scale}x");
+        }
+    }
+}
+
+#[test]
+fn corners_are_clamped_to_the_header() {
+    let render = |width, corner_radius| {
+        let state = RenderState {
+            shadows: false,
+            corner_radius,
+            ..RenderState::new((width, 10), 1)
+        };
+        render_decorations(&state).unwrap()
+    };
+    // Just inside the header, where rounded corners leave the background transparent.
+    let corner = |pixmap: &Pixmap| pixmap.pixel(BORDER_SIZE, BORDER_SIZE).unwrap().alpha();
+    let middle = |pixmap: &Pixmap, x| pixmap.pixel(x, BORDER_SIZE + HEADER_SIZE / 2).unwrap();
+
+    assert_eq!(corner(&render(100, CORNER_RADIUS)), 0);
+
+    // Narrower than both corners, they meet in the middle rather than cross.
+    let narrow = render(10, CORNER_RADIUS);
+    assert!(corner(&narrow) < 255);
+    assert_eq!(middle(&narrow, BORDER_SIZE + 5).alpha(), 255);
+
+    // Taller corners than the header leave it filled down to its bottom.
+    let round = render(300, 100.0);
+    assert_eq!(corner(&round), 0);
+    assert_eq!(middle(&round, BORDER_SIZE + 150).alpha(), 255);
+    assert_eq!(
+        round.pixel(BORDER_SIZE + 1, BORDER_SIZE + HEADER_SIZE - 2),
+        render(300, CORNER_RADIUS).pixel(BORDER_SIZE + 1, BORDER_SIZE + HEADER_SIZE - 2)
+    );
+}
+
+#[test]
+fn draw_into_canvas_region() {
+    let state = RenderState::new((200, 100), 1);
+    let decorations = render_decorations(&state).unwrap();
+
+    let mut canvas = Pixmap::new(decorations.width() + 30, decorations.height() + 20).unwrap();
+    draw_decorations(&state, &mut canvas.as_mut(), (30, 20)).unwrap();
+    let x = BORDER_SIZE + 20;
+    let y = BORDER_SIZE + 20;
+    assert_eq!(canvas.pixel(x + 30, y + 20), decorations.pixel(x, y));
+    assert_eq!(canvas.pixel(x, y).unwrap().alpha(), 0);
+}
+
+#[test]
+fn reserved_area_is_left_to_the_app() {
+    let state = RenderState {
+        reserved_area: Some(LogicalRect::new(50, 0, 60, HEADER_SIZE)),
+        ..RenderState::new((200, 100), 1)
+    };
+    let pixmap = render_decorations(&state).unwrap();
+    let border = BORDER_SIZE;
+    assert_eq!(pixmap.pixel(border + 80, border + 10).unwrap().alpha(), 0);
+    assert_eq!(pixmap.pixel(border + 20, border + 10).unwrap().alpha(), 255);
+
+    let y = f64::from(border + 10);
+    assert_eq!(
+        hit_test(&state, f64::from(border + 80), y),
+        DecorationHit::None
+    );
+    assert_eq!(
+        hit_test(&state, f64::from(border + 20), y),
+        DecorationHit::Header
+    );
+}
+
+#[test]
+fn hit_test_follows_layout() {
+    let state = RenderState::new((200, 100), 2);
+    let border = f64::from(BORDER_SIZE);
+    let header = f64::from(HEADER_SIZE);
+
+    assert_eq!(hit_test(&state, 50.0, border + 5.0), DecorationHit::Header);
+    assert_eq!(
+        hit_test(&state, 50.0, border + header + 50.0),
+        DecorationHit::None
+    );
+    assert_eq!(
+        hit_test(&state, 2.0, border + header + 50.0),
+        DecorationHit::Resize(ResizeEdge::Left)
+    );
+    assert_eq!(
+        hit_test(&state, 100.0, border + header + 100.0 + 2.0),
+        DecorationHit::Resize(ResizeEdge::Bottom)
+    );
+    assert_eq!(
+        hit_test(&state, 100.0, 2.0),
+        DecorationHit::Resize(ResizeEdge::Top)
+    );
+
+    let mut buttons = Buttons::default();
+    buttons.arrange(200 + 2 * BORDER_SIZE);
+    let close = &buttons.close;
+    assert_eq!(
+        hit_test(&state, close.center_x().into(), close.center_y().into()),
+        DecorationHit::Button(ButtonKind::Close)
+    );
+
+    let fixed = RenderState {
+        resizable: false,
+        ..state.clone()
+    };
+    assert_eq!(hit_test(&fixed, 100.0, 2.0), DecorationHit::Header);
+    assert_eq!(
+        hit_test(&fixed, 2.0, border + header + 50.0),
+        DecorationHit::None
+    );
+
+    let maximized = RenderState {
+        maximized: true,
+        ..state.clone()
+    };
+    assert_eq!(
+        hit_test(&maximized, 2.0, border + header + 50.0),
+        DecorationHit::None
+    );
+}
+
+#[test]
+fn pathological_sizes_are_clamped() {
+    assert_eq!(sanitize_size((0, 0)), (1, 1));
+    assert_eq!(sanitize_size((800, 600)), (800, 600));
+    assert_eq!(sanitize_size((-5i32 as u32, 600)), (1, 600));
+    assert_eq!(sanitize_size((u32::MAX, u32::MAX)), (1, 1));
+    assert_eq!(
+        sanitize_size((i32::MAX as u32, 600)),
+        (MAX_CONTENT_SIZE.0, 600)
+    );
+
+    for size in [
+        (0, 0),
+        (1, 1),
+        (2 * BORDER_SIZE - 1, 1),
+        (1, 2 * BORDER_SIZE - 1),
+    ] {
+        let state = RenderState::new(sanitize_size(size), 2);
+        assert!(render_decorations(&state).is_some(), "{size:?}");
+        for x in 0..50 {
+            for y in 0..80 {
+                hit_test(&state, x as f64, y as f64);
+            }
+        }
+    }
+    for size in [(i32::MAX as u32, 100), (100, i32::MAX as u32)] {
+        let state = RenderState::new(sanitize_size(size), 1);
+        assert!(render_decorations(&state).is_some(), "{size:?}");
+    }
+    // Too large for a pixmap, but without overflowing.
+    let state = RenderState::new(MAX_CONTENT_SIZE, u32::MAX);
+    assert!(render_decorations(&state).is_none());
+}
+
+#[test]
+fn clicks_on_the_decorations() {
+    let header = DecorationHit::Header;
+    assert_eq!(
+        header.press_request(false, false),
+        Some(PointerRequest::Move)
+    );
+    assert_eq!(
+        header.press_request(true, false),
+        Some(PointerRequest::Maximize)
+    );
+    assert_eq!(
+        header.press_request(true, true),
+        Some(PointerRequest::UnMaximize)
+    );
+    assert_eq!(
+        DecorationHit::Resize(ResizeEdge::Left).press_request(true, false),
+        Some(PointerRequest::Resize(ResizeEdge::Left))
+    );
+    let close = DecorationHit::Button(ButtonKind::Close);
+    assert_eq!(close.press_request(false, false), None);
+    assert_eq!(ButtonKind::Close.request(false), PointerRequest::Close);
+    assert_eq!(
+        ButtonKind::Maximize.request(true),
+        PointerRequest::UnMaximize
+    );
+}
+
+#[test]
+fn single_axis_maximize_keeps_other_borders() {
+    let border = f64::from(BORDER_SIZE);
+    let header = f64::from(HEADER_SIZE);
+    let horizontal = RenderState {
+        tiled: Edges {
+            left: true,
+            right: true,
+            ..Edges::NONE
+        },
+        ..RenderState::new((200, 100), 1)
+    };
+    assert_eq!(
+        hit_test(&horizontal, 2.0, border + header + 50.0),
+        DecorationHit::None
+    );
+    let bottom = border + header + 100.0 + 2.0;
+    assert_eq!(
+        hit_test(&horizontal, 2.0, bottom),
+        DecorationHit::Resize(ResizeEdge::Bottom)
+    );
+    assert_eq!(
+        hit_test(&horizontal, 100.0, 2.0),
+        DecorationHit::Resize(ResizeEdge::Top)
+    );
+
+    let vertical = RenderState {
+        tiled: Edges {
+            top: true,
+            bottom: true,
+            ..Edges::NONE
+        },
+        ..horizontal
+    };
+    assert_eq!(hit_test(&vertical, 100.0, 2.0), DecorationHit::Header);
+    assert_eq!(hit_test(&vertical, 100.0, bottom), DecorationHit::None);
+    assert_eq!(
+        hit_test(&vertical, 2.0, border + header + 50.0),
+        DecorationHit::Resize(ResizeEdge::Left)
+    );
+    assert_eq!(
+        hit_test(&vertical, 2.0, bottom),
+        DecorationHit::Resize(ResizeEdge::Left)
+    );
+
+    let pixmap = render_decorations(&vertical).unwrap();
+    let x = BORDER_SIZE + 100;
+    let y = BORDER_SIZE + HEADER_SIZE + 100;
+    assert_eq!(pixmap.pixel(x, y).unwrap().alpha(), 0);
+    assert_eq!(pixmap.pixel(BORDER_SIZE - 1, y - 50).unwrap().alpha(), 255);
+}
+
+#[test]
+fn tiled_edges_lose_border_and_corner() {
+    let state = RenderState {
+        shadows: false,
+        tiled: Edges {
+            left: true,
+            ..Edges::NONE
+        },
+        ..RenderState::new((200, 100), 1)
+    };
+    let pixmap = render_decorations(&state).unwrap();
+    let y = BORDER_SIZE + HEADER_SIZE + 50;
+    assert_eq!(pixmap.pixel(BORDER_SIZE - 1, y).unwrap().alpha(), 0);
+    assert_eq!(pixmap.pixel(BORDER_SIZE + 200, y).unwrap().alpha(), 255);
+    // Just inside the header, the left corner is square, the right one rounded.
+    assert_eq!(pixmap.pixel(BORDER_SIZE, BORDER_SIZE).unwrap().alpha(), 255);
+    let right = BORDER_SIZE + 199;
+    assert_eq!(pixmap.pixel(right, BORDER_SIZE).unwrap().alpha(), 0);
+
+    let border = f64::from(BORDER_SIZE);
+    let header = f64::from(HEADER_SIZE);
+    assert_eq!(
+        hit_test(&state, 2.0, border + header + 50.0),
+        DecorationHit::None
+    );
+    assert_eq!(
+        hit_test(&state, 2.0, 2.0),
+        DecorationHit::Resize(ResizeEdge::Top)
+    );
+}
+
+#[test]
+fn floating_buttons_cover_only_the_buttons() {
+    let state = RenderState {
+        shadows: false,
+        header_mode: HeaderMode::ButtonsOnly,
+        ..RenderState::new((200, 100), 1)
+    };
+    let pixmap = render_decorations(&state).unwrap();
+    assert_eq!(pixmap.height(), 100 + 2 * BORDER_SIZE);
+
+    let buttons = state.buttons();
+    let close = &buttons.close;
+    let (x, y) = (close.center_x(), close.center_y());
+    assert_eq!(pixmap.pixel(x as u32, y as u32).unwrap().alpha(), 255);
+    assert_eq!(
+        pixmap
+            .pixel(BORDER_SIZE + 20, BORDER_SIZE + 5)
+            .unwrap()
+            .alpha(),
+        0
+    );
+
+    let border = f64::from(BORDER_SIZE);
+    assert_eq!(
+        hit_test(&state, x.into(), y.into()),
+        DecorationHit::Button(ButtonKind::Close)
+    );
+    assert_eq!(hit_test(&state, 50.0, border + 5.0), DecorationHit::None);
+    assert_eq!(
+        hit_test(&state, 50.0, 2.0),
+        DecorationHit::Resize(ResizeEdge::Top)
+    );
+    assert_eq!(
+        hit_test(&state, 2.0, border + 50.0),
+        DecorationHit::Resize(ResizeEdge::Left)
+    );
+    assert_eq!(
+        hit_test(&state, 50.0, border + 100.0 + 2.0),
+        DecorationHit::Resize(ResizeEdge::Bottom)
+    );
+}
+
+#[test]
+fn translucent_button_background_is_restored() {
+    let mut colors = ColorTheme::light().active;
+    colors.headerbar = Color::from_rgba8(235, 235, 235, 128);
+
+    let mut pixmap = Pixmap::new(4, 4).unwrap();
+    pixmap.fill(colors.headerbar);
+    let background = pixmap.clone();
+
+    let rect = Rect::from_xywh(0.0, 0.0, 4.0, 4.0).unwrap();
+    for _ in 0..2 {
+        pixmap.fill_rect(
+            rect,
+            &button_clear_paint(&colors, HeaderMode::Full),
+            Transform::identity(),
+            None,
+        );
+    }
+    assert_eq!(pixmap, background);
+}
+
+#[test]
+fn parts_surround_the_content() {
+    let sizes = Sizes::default();
+    let size = (200, 100);
+    let border = BORDER_SIZE as i32;
+    let header = part_origin(DecorationPart::Header, sizes, size);
+    assert_eq!(header, (-border, -(HEADER_SIZE as i32) - border));
+    let bottom = part_origin(DecorationPart::Bottom, sizes, size);
+    assert_eq!(bottom, (-border, 100));
+    assert_eq!(part_origin(DecorationPart::Left, sizes, size), (-border, 0));
+    assert_eq!(part_origin(DecorationPart::Right, sizes, size), (200, 0));
+}
@@ -0,0 +1,158 @@
+//! Read-only access to the user's dconf database, for systems without the `gsettings` tool.
+//!
+//! The database is a GVDB file: a hash table of items, each holding a fragment of its key and
+//! either a serialized `GVariant` value or the index of its parent item. Values in dconf are
+//! stored as variants, only strings are decoded.
+use std::{env, fs, path::PathBuf};
+
+/// Size of a hash item: hash, parent, key start, key size, type, padding and value pointer.
+const ITEM_SIZE: usize = 24;
+/// Items without a parent.
+const NO_PARENT: u32 = u32::MAX;
+/// Deepest key nesting followed, guarding against cycles in broken databases.
+const MAX_DEPTH: usize = 64;
+
+/// The string stored at `key`, like `/org/gnome/desktop/wm/preferences/titlebar-font`.
+///
+/// Only the user database is read, system defaults in `/etc/dconf` are not.
+pub(crate) fn read_string(key: &str) -> Option<String> {
+    let db = fs::read(user_database()?).ok()?;
+    let value = lookup(&db, key)?;
+    variant_str(value).map(String::from)
+}
+
+fn user_database() -> Option<PathBuf> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("dconf").join("user"))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// Bytes between the `start` and `end` offsets stored at `offset`.
+fn pointer_at(data: &[u8], offset: usize) -> Option<&[u8]> {
+    let start = u32_at(data, offset)? as usize;
+    let end = u32_at(data, offset + 4)? as usize;
+    data.get(start..end)
+}
+
+/// Serialized value of `key`, if it is a value rather than a directory.
+fn lookup<'a>(db: &'a [u8], key: &str) -> Option<&'a [u8]> {
+    // Only little-endian databases, as written on the usual hosts, are understood.
+    if db.get(..8)? != b"GVariant" {
+        return None;
+    }
+    let root = pointer_at(db, 16)?;
+    let root_start = u32_at(db, 16)? as usize;
+
+    let n_bloom_words = u32_at(root, 0)? & ((1 << 27) - 1);
+    let n_buckets = u32_at(root, 4)?;
+    let items_start = 8 + 4 * (n_bloom_words as usize + n_buckets as usize);
+    let items = root.get(items_start..)?;
+    let item = |id: usize| items.get(id * ITEM_SIZE..(id + 1) * ITEM_SIZE);
+
+    // Keys are split into fragments along their parents, matches are found by walking up from
+    // each value item and stripping the fragments off the end of `key`.
+    let matches = |id: usize| {
+        let mut rest = key;
+        let mut id = id;
+        for _ in 0..MAX_DEPTH {
+            let item = item(id)?;
+            let key_start = u32_at(item, 8)? as usize;
+            let key_size = u16::from_le_bytes([item[12], item[13]]) as usize;
+            let fragment = db.get(key_start..key_start + key_size)?;
+            rest = rest.strip_suffix(std::str::from_utf8(fragment).ok()?)?;
+
+            match u32_at(item, 4)? {
+                NO_PARENT => return rest.is_empty().then_some(()),
+                parent => id = parent as usize,
+            }
+        }
+        None
+    };
+
+    (0..items.len() / ITEM_SIZE)
+        .filter(|&id| item(id).is_some_and(|item| item[14] == b'v'))
+        .find(|&id| matches(id).is_some())
+        .and_then(|id| pointer_at(db, root_start + items_start + id * ITEM_SIZE + 16))
+}
+
+/// The string inside of a serialized variant: its value, a nul byte and its type.
+fn variant_str(variant: &[u8]) -> Option<&str> {
+    let split = variant.iter().rposition(|&b| b == 0)?;
+    let (value, ty) = (&variant[..split], &variant[split + 1..]);
+    if ty != b"s" {
+        return None;
+    }
+    // Strings are nul terminated themselves.
+    std::str::from_utf8(value.strip_suffix(&[0])?).ok()
+}
+
+#[cfg(test)]
+fn database(items: &[(u32, &str, Option<&str>)]) -> Vec<u8> {
+    let root_start = 24;
+    let items_start = root_start + 8 + 4;
+    let mut strings = items_start + ITEM_SIZE * items.len();
+    let mut db = b"GVariant".to_vec();
+    db.extend([0u32, 0].iter().flat_map(|v| v.to_le_bytes()));
+    let root_end = strings
+        + items
+            .iter()
+            .map(|(_, key, value)| key.len() + value.map_or(0, |v| v.len() + 3))
+            .sum::<usize>();
+    db.extend((root_start as u32).to_le_bytes());
+    db.extend((root_end as u32).to_le_bytes());
+    // No bloom filter and a single bucket.
+    db.extend([0u32, 1, 0].iter().flat_map(|v| v.to_le_bytes()));
+
+    let mut data = Vec::new();
+    for (parent, key, value) in items {
+        db.extend(0u32.to_le_bytes());
+        db.extend(parent.to_le_bytes());
+        db.extend((strings as u32).to_le_bytes());
+        db.extend((key.len() as u16).to_le_bytes());
+        db.push(if value.is_some() { b'v' } else { b'L' });
+        db.push(0);
+        data.extend(key.bytes());
+        strings += key.len();
+        let value = value.map(|v| [v.as_bytes(), b"\0\0s"].concat());
+        let len = value.as_ref().map_or(0, Vec::len);
+        db.extend((strings as u32).to_le_bytes());
+        db.extend(((strings + len) as u32).to_le_bytes());
+        data.extend(value.unwrap_or_default());
+        strings += len;
+    }
+    db.extend(data);
+    db
+}
+
+#[test]
+fn nested_keys_are_found() {
+    let db = database(&[
+        (NO_PARENT, "/org/gnome/desktop/wm/", None),
+        (0, "preferences/", None),
+        (1, "titlebar-font", Some("Cantarell Bold 11")),
+        (1, "button-layout", Some("appmenu:close")),
+    ]);
+    let value = lookup(&db, "/org/gnome/desktop/wm/preferences/titlebar-font").unwrap();
+    assert_eq!(variant_str(value), Some("Cantarell Bold 11"));
+    let value = lookup(&db, "/org/gnome/desktop/wm/preferences/button-layout").unwrap();
+    assert_eq!(variant_str(value), Some("appmenu:close"));
+
+    assert!(lookup(&db, "/org/gnome/desktop/wm/preferences/").is_none());
+    assert!(lookup(&db, "/other/titlebar-font").is_none());
+    assert!(lookup(&db[..40], "/org/gnome/desktop/wm/preferences/titlebar-font").is_none());
+    assert!(lookup(b"not a database", "/").is_none());
+}
+
+#[test]
+fn only_string_variants_are_read() {
+    assert_eq!(variant_str(b"on\0\0s"), Some("on"));
+    assert_eq!(variant_str(b"\x01\0b"), None);
+    assert_eq!(variant_str(b""), None);
+}
@@ -0,0 +1,84 @@
+//! Failures of the decorations.
+use std::{error, fmt, io};
+
+/// Why the decorations couldn't be set up or drawn.
+#[derive(Debug)]
+pub enum DecorationError {
+    /// Creating or growing the shm pool failed, e.g. because of memory or fd limits.
+    Shm(io::Error),
+    /// The compositor doesn't advertise a global the decorations need, in a usable version.
+    MissingGlobal(&'static str),
+    /// A decoration buffer exceeds the size a shm pool can hold.
+    BufferTooLarge { width: u32, height: u32 },
+    /// tiny-skia failed to draw the decorations.
+    Render,
+}
+
+impl DecorationError {
+    /// Whether the next redraw may succeed, e.g. at a smaller size or after memory got freed.
+    ///
+    /// Without the globals no decorations can be drawn at all.
+    pub fn is_recoverable(&self) -> bool {
+        !matches!(self, DecorationError::MissingGlobal(_))
+    }
+}
+
+impl fmt::Display for DecorationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecorationError::Shm(err) => write!(f, "decoration shm pool failed: {err}"),
+            DecorationError::MissingGlobal(name) => {
+                write!(f, "compositor doesn't support {name}")
+            }
+            DecorationError::BufferTooLarge { width, height } => {
+                write!(f, "decoration buffer of {width}x{height} is too large")
+            }
+            DecorationError::Render => write!(f, "failed to draw decorations"),
+        }
+    }
+}
+
+impl error::Error for DecorationError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            DecorationError::Shm(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DecorationError {
+    fn from(err: io::Error) -> Self {
+        DecorationError::Shm(err)
+    }
+}
+
+#[test]
+fn missing_globals_are_fatal() {
+    assert!(!DecorationError::MissingGlobal("wl_shm").is_recoverable());
+    assert!(DecorationError::Render.is_recoverable());
+    let err = DecorationError::from(io::Error::from(io::ErrorKind::OutOfMemory));
+    assert!(err.is_recoverable());
+    assert!(error::Error::source(&err).is_some());
+}
+/// Stride of an ARGB buffer of `width` × `height` pixels, if it fits in a shm pool.
+pub(crate) fn buffer_stride(width: u32, height: u32) -> Result<i32, DecorationError> {
+    4u32.checked_mul(width)
+        .filter(|stride| {
+            stride
+                .checked_mul(height)
+                .is_some_and(|len| len <= i32::MAX as u32)
+        })
+        .map(|stride| stride as i32)
+        .ok_or(DecorationError::BufferTooLarge { width, height })
+}
+
+#[test]
+fn oversized_buffers_are_rejected() {
+    assert_eq!(buffer_stride(100, 10).unwrap(), 400);
+    assert!(matches!(
+        buffer_stride(1 << 15, 1 << 15),
+        Err(DecorationError::BufferTooLarge { .. })
+    ));
+    assert!(buffer_stride(u32::MAX, 1).is_err());
+}
@@ -0,0 +1,702 @@
+//! Pointer input on the decorations: a state machine independent of the wayland objects, and
+//! the glue feeding it the events of the frame's pointers.
+use smithay_client_toolkit::{
+    reexports::{
+        client::protocol::{wl_pointer, wl_seat::WlSeat},
+        protocols::xdg_shell::client::xdg_toplevel::ResizeEdge,
+    },
+    seat::pointer::ThemedPointer,
+    window::FrameRequest,
+};
+
+use crate::{
+    buttons::{ButtonKind, Buttons},
+    core::{
+        self, location_hit, precise_location, reserve, DecorationHit, Edges, HeaderMode,
+        PointerRequest,
+    },
+    logging::error,
+    parts::DecorationPartKind,
+    theme::Sizes,
+    ButtonScroll, DecorationEvent, Inner, Location, LogicalRect, MaximizeAxis, Outgoing,
+};
+
+/// Distance in logical pixels the pointer travels with the left button held on the titlebar
+/// before the window moves, like the GTK default. Shorter presses are clicks, e.g. touchpad taps
+/// of a double tap.
+const DRAG_THRESHOLD: f64 = 8.0;
+
+/// Double click detection.
+///
+/// Uses the timestamps of the button events rather than the time of dispatch: touchpad taps are
+/// only reported once the tap timeout expired, so both taps of a double tap may be dispatched
+/// in one go.
+#[derive(Debug, Default)]
+struct ClickTracker {
+    last: Option<(Location, u32)>,
+}
+
+impl ClickTracker {
+    const DOUBLE_CLICK_MS: u32 = 400;
+
+    /// Record a click, returns `true` if it completes a double click.
+    fn click(&mut self, location: Location, time: u32) -> bool {
+        match self.last.replace((location, time)) {
+            Some((last_location, last_time))
+                if last_location == location
+                    && time.wrapping_sub(last_time) < Self::DOUBLE_CLICK_MS =>
+            {
+                self.last = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl PointerRequest {
+    fn with_seat(self, seat: &WlSeat) -> FrameRequest {
+        match self {
+            PointerRequest::Move => FrameRequest::Move(seat.clone()),
+            PointerRequest::Resize(edge) => FrameRequest::Resize(seat.clone(), edge.into()),
+            PointerRequest::ShowMenu { x, y } => FrameRequest::ShowMenu(seat.clone(), x, y),
+            PointerRequest::Maximize => FrameRequest::Maximize,
+            PointerRequest::UnMaximize => FrameRequest::UnMaximize,
+            PointerRequest::Minimize => FrameRequest::Minimize,
+            PointerRequest::Close => FrameRequest::Close,
+            PointerRequest::Refresh => FrameRequest::Refresh,
+        }
+    }
+}
+
+/// What the pointer handlers ask of the frame and its callbacks, in order.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PointerOutput {
+    Request(PointerRequest),
+    /// For [`AdwaitaConfig::decoration_events`](crate::AdwaitaConfig::decoration_events).
+    Event(DecorationEvent),
+    /// For [`AdwaitaConfig::axis_maximize`](crate::AdwaitaConfig::axis_maximize).
+    AxisMaximize(MaximizeAxis),
+    /// For [`AdwaitaConfig::titlebar_axis`](crate::AdwaitaConfig::titlebar_axis).
+    TitlebarAxis(wl_pointer::Axis, f64),
+}
+
+/// A pointer event, with the surface it refers to already looked up.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum PointerInput {
+    Enter {
+        /// The decoration part entered, [`Location::None`] for other surfaces.
+        surface: Location,
+        /// The window content was entered.
+        on_content: bool,
+        x: f64,
+        y: f64,
+    },
+    Leave,
+    Motion {
+        x: f64,
+        y: f64,
+    },
+    Button {
+        time: u32,
+        button: u32,
+        pressed: bool,
+    },
+    Axis {
+        axis: wl_pointer::Axis,
+        value: f64,
+    },
+}
+
+/// The state of the frame the pointer handlers act on.
+pub(crate) struct PointerFrame<'a> {
+    pub buttons: &'a Buttons,
+    /// Width of the window content.
+    pub width: u32,
+    pub hidden: Edges,
+    pub header_mode: HeaderMode,
+    pub resizable: bool,
+    pub maximized: bool,
+    /// Whether the callbacks are set, the frame falls back to other behaviour without them.
+    pub axis_maximize: bool,
+    pub titlebar_axis: bool,
+    pub button_scroll: ButtonScroll,
+    pub drag_regions: &'a [LogicalRect],
+    pub reserved_area: Option<LogicalRect>,
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl<'a> PointerFrame<'a> {
+    /// The window of `state`, without drag regions or callbacks.
+    pub fn new(state: &crate::RenderState, buttons: &'a Buttons) -> Self {
+        Self {
+            buttons,
+            width: state.size.0,
+            hidden: state.hidden_borders(),
+            header_mode: state.header_mode,
+            resizable: state.resizable,
+            maximized: state.maximized,
+            axis_maximize: false,
+            titlebar_axis: false,
+            button_scroll: ButtonScroll::default(),
+            drag_regions: &[],
+            reserved_area: state.reserved_area,
+        }
+    }
+}
+
+/// What a single pointer is over and doing, independent of the wayland objects.
+#[derive(Debug)]
+pub(crate) struct PointerState {
+    pub location: Location,
+    /// The pointer is over the window content rather than the decorations.
+    on_content: bool,
+    position: (f64, f64),
+    clicks: ClickTracker,
+    /// A move was requested and the pointer hasn't been seen since.
+    moving: bool,
+    /// Where the left button was pressed on the titlebar, the window moves once the pointer got
+    /// [`DRAG_THRESHOLD`] away from it.
+    move_from: Option<(f64, f64)>,
+    lpm_grab: Option<ButtonKind>,
+}
+
+impl PointerState {
+    pub fn new() -> Self {
+        Self {
+            location: Location::None,
+            on_content: false,
+            position: (0.0, 0.0),
+            clicks: ClickTracker::default(),
+            moving: false,
+            move_from: None,
+            lpm_grab: None,
+        }
+    }
+
+    pub fn input(
+        &mut self,
+        input: PointerInput,
+        frame: &PointerFrame,
+        output: &mut Vec<PointerOutput>,
+    ) {
+        // The compositor holds the pointer during the move, some leave it beforehand.
+        if self.moving && input != PointerInput::Leave {
+            self.moving = false;
+            output.push(PointerOutput::Event(DecorationEvent::DragEnded));
+        }
+
+        match input {
+            PointerInput::Enter {
+                surface,
+                on_content,
+                x,
+                y,
+            } => {
+                self.on_content = on_content;
+                self.location = self.locate(surface, frame, x, y);
+                self.position = (x, y);
+                hover_events(Location::None, self.location, output);
+            }
+            PointerInput::Leave => {
+                self.on_content = false;
+                self.move_from = None;
+                hover_events(self.location, Location::None, output);
+                self.location = Location::None;
+                output.push(PointerOutput::Request(PointerRequest::Refresh));
+            }
+            PointerInput::Motion { x, y } => {
+                self.position = (x, y);
+                self.relocate(frame, output);
+
+                if let Some((from_x, from_y)) = self.move_from {
+                    if (x - from_x).hypot(y - from_y) >= DRAG_THRESHOLD {
+                        self.move_from = None;
+                        self.request(PointerRequest::Move, output);
+                    }
+                }
+            }
+            PointerInput::Button {
+                time,
+                button,
+                pressed,
+            } => {
+                // The window may have been resized under the pointer, e.g. dropping buttons
+                // which no longer fit.
+                self.relocate(frame, output);
+
+                let request = if pressed {
+                    match button {
+                        // Left mouse button.
+                        0x110 => lmb_press(self, frame, time, output),
+                        // Right mouse button.
+                        0x111 => rmb_press(self, frame.header_mode.geometry(frame.buttons.sizes())),
+                        _ => None,
+                    }
+                } else {
+                    // Left mouse button.
+                    if button == 0x110 {
+                        lmb_release(self, frame, output)
+                    } else {
+                        None
+                    }
+                };
+
+                match request {
+                    // Left to the motion, the press may be a click.
+                    Some(PointerRequest::Move) => self.move_from = Some(self.position),
+                    Some(request) => self.request(request, output),
+                    None => (),
+                }
+            }
+            PointerInput::Axis { axis, value } => {
+                let forward = match self.location {
+                    Location::Head => true,
+                    Location::Button(_) => frame.button_scroll == ButtonScroll::Forward,
+                    _ => false,
+                };
+
+                if forward && frame.titlebar_axis {
+                    output.push(PointerOutput::TitlebarAxis(axis, value));
+                }
+            }
+        }
+    }
+
+    /// Asks `request` of the frame, with the event announcing it.
+    fn request(&mut self, request: PointerRequest, output: &mut Vec<PointerOutput>) {
+        match request {
+            PointerRequest::Move => {
+                self.moving = true;
+                output.push(PointerOutput::Event(DecorationEvent::DragStarted));
+            }
+            PointerRequest::Resize(edge) => {
+                output.push(PointerOutput::Event(DecorationEvent::ResizeStarted(edge)))
+            }
+            PointerRequest::ShowMenu { x, y } => {
+                output.push(PointerOutput::Event(DecorationEvent::MenuRequested {
+                    x,
+                    y,
+                }))
+            }
+            _ => (),
+        }
+        output.push(PointerOutput::Request(request));
+    }
+
+    /// Updates the location at the current position.
+    fn relocate(&mut self, frame: &PointerFrame, output: &mut Vec<PointerOutput>) {
+        let (x, y) = self.position;
+        let newpos = self.locate(self.location, frame, x, y);
+        if newpos != self.location {
+            match (newpos, self.location) {
+                (Location::Button(_), _) | (_, Location::Button(_)) => {
+                    // pointer movement involves a button, request refresh
+                    output.push(PointerOutput::Request(PointerRequest::Refresh));
+                }
+                _ => (),
+            }
+            hover_events(self.location, newpos, output);
+            self.location = newpos;
+        }
+    }
+
+    /// Location of the pointer on the surface it is over, `old` tells which part that is.
+    fn locate(&self, old: Location, frame: &PointerFrame, x: f64, y: f64) -> Location {
+        if self.on_content {
+            drag_location(frame.drag_regions, x, y)
+        } else {
+            let location = precise_location(frame.buttons, old, frame.width, frame.hidden, x, y);
+            reserve(
+                location,
+                frame.reserved_area,
+                frame.buttons.sizes().border,
+                x,
+                y,
+            )
+        }
+    }
+}
+
+pub(crate) struct PointerUserData {
+    pub state: PointerState,
+    current_surface: DecorationPartKind,
+    pub seat: WlSeat,
+    /// Serial of the last button event, a move started by motion belongs to its press.
+    button_serial: u32,
+}
+
+impl PointerUserData {
+    pub fn new(seat: WlSeat) -> Self {
+        Self {
+            state: PointerState::new(),
+            current_surface: DecorationPartKind::None,
+            seat,
+            button_serial: 0,
+        }
+    }
+
+    pub fn event(
+        &mut self,
+        event: wl_pointer::Event,
+        inner: &mut Inner,
+        buttons: &Buttons,
+        pointer: &ThemedPointer,
+    ) {
+        use wl_pointer::Event;
+        let (input, serial) = match event {
+            Event::Enter {
+                serial,
+                surface,
+                surface_x,
+                surface_y,
+            } => {
+                self.current_surface = inner.parts.find_decoration_part(&surface);
+                let input = PointerInput::Enter {
+                    surface: inner.parts.find_surface(&surface),
+                    on_content: surface.as_ref().equals(inner.content.as_ref()),
+                    x: surface_x,
+                    y: surface_y,
+                };
+                (input, Some(serial))
+            }
+            Event::Leave { serial, .. } => {
+                self.current_surface = DecorationPartKind::None;
+                (PointerInput::Leave, Some(serial))
+            }
+            Event::Motion {
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                let input = PointerInput::Motion {
+                    x: surface_x,
+                    y: surface_y,
+                };
+                (input, None)
+            }
+            Event::Button {
+                serial,
+                time,
+                button,
+                state,
+            } => {
+                let input = PointerInput::Button {
+                    time,
+                    button,
+                    pressed: state == wl_pointer::ButtonState::Pressed,
+                };
+                self.button_serial = serial;
+                (input, Some(serial))
+            }
+            Event::Axis { axis, value, .. } => (PointerInput::Axis { axis, value }, None),
+            _ => return,
+        };
+
+        let old_location = self.state.location;
+        let mut output = Vec::new();
+        self.state
+            .input(input, &inner.pointer_frame(buttons), &mut output);
+
+        for output in output {
+            match output {
+                PointerOutput::Request(PointerRequest::Refresh) => {
+                    inner.dirty = true;
+                    inner.request(FrameRequest::Refresh, 0);
+                }
+                PointerOutput::Request(request) => inner.request(
+                    request.with_seat(&self.seat),
+                    serial.unwrap_or(self.button_serial),
+                ),
+                PointerOutput::Event(event) => inner.send_event(event),
+                PointerOutput::AxisMaximize(axis) => {
+                    inner.outgoing.push_back(Outgoing::AxisMaximize(axis))
+                }
+                PointerOutput::TitlebarAxis(axis, value) => inner
+                    .outgoing
+                    .push_back(Outgoing::TitlebarAxis(axis, value)),
+            }
+        }
+
+        match input {
+            PointerInput::Enter { .. } | PointerInput::Leave => {
+                change_pointer(pointer, inner, self.state.location, serial)
+            }
+            // we changed of part of the decoration, pointer image may need to be changed
+            PointerInput::Motion { .. } | PointerInput::Button { .. }
+                if self.state.location != old_location =>
+            {
+                change_pointer(pointer, inner, self.state.location, None)
+            }
+            _ => (),
+        }
+    }
+}
+
+impl From<core::ResizeEdge> for ResizeEdge {
+    fn from(edge: core::ResizeEdge) -> Self {
+        match edge {
+            core::ResizeEdge::Top => ResizeEdge::Top,
+            core::ResizeEdge::TopRight => ResizeEdge::TopRight,
+            core::ResizeEdge::Right => ResizeEdge::Right,
+            core::ResizeEdge::BottomRight => ResizeEdge::BottomRight,
+            core::ResizeEdge::Bottom => ResizeEdge::Bottom,
+            core::ResizeEdge::BottomLeft => ResizeEdge::BottomLeft,
+            core::ResizeEdge::Left => ResizeEdge::Left,
+            core::ResizeEdge::TopLeft => ResizeEdge::TopLeft,
+        }
+    }
+}
+
+/// Content is only part of the decorations within one of the drag regions.
+fn drag_location(regions: &[LogicalRect], x: f64, y: f64) -> Location {
+    if regions.iter().any(|region| region.contains(x, y)) {
+        Location::Drag
+    } else {
+        Location::None
+    }
+}
+
+fn lmb_press(
+    pointer: &mut PointerState,
+    frame: &PointerFrame,
+    time: u32,
+    output: &mut Vec<PointerOutput>,
+) -> Option<PointerRequest> {
+    let double_click = pointer.clicks.click(pointer.location, time);
+
+    if double_click && frame.resizable {
+        let axis = match pointer.location {
+            Location::Left | Location::Right => Some(MaximizeAxis::Horizontal),
+            Location::Top | Location::Bottom => Some(MaximizeAxis::Vertical),
+            _ => None,
+        };
+
+        if let Some(axis) = axis.filter(|_| frame.axis_maximize) {
+            output.push(PointerOutput::AxisMaximize(axis));
+            return None;
+        }
+    }
+
+    match location_hit(pointer.location, frame.resizable) {
+        DecorationHit::Button(btn) => {
+            pointer.lpm_grab = Some(btn);
+            output.push(PointerOutput::Event(DecorationEvent::ButtonPressed(btn)));
+            None
+        }
+        hit => hit.press_request(double_click, frame.maximized),
+    }
+}
+
+fn lmb_release(
+    pointer: &mut PointerState,
+    frame: &PointerFrame,
+    output: &mut Vec<PointerOutput>,
+) -> Option<PointerRequest> {
+    pointer.move_from = None;
+    let lpm_grab = pointer.lpm_grab.take();
+    if let Some(btn) = lpm_grab {
+        output.push(PointerOutput::Event(DecorationEvent::ButtonReleased(btn)));
+    }
+
+    match pointer.location {
+        Location::Button(btn) => {
+            if lpm_grab == Some(btn) {
+                Some(btn.request(frame.maximized))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+fn rmb_press(pointer: &PointerState, sizes: Sizes) -> Option<PointerRequest> {
+    match pointer.location {
+        Location::Head | Location::Button(_) => Some(PointerRequest::ShowMenu {
+            x: pointer.position.0 as i32 - sizes.border as i32,
+            // We must offset it by header size for precise position, floating buttons have
+            // none.
+            y: pointer.position.1 as i32 - (sizes.header as i32 + sizes.border as i32),
+        }),
+        // Already in content coordinates.
+        Location::Drag => Some(PointerRequest::ShowMenu {
+            x: pointer.position.0 as i32,
+            y: pointer.position.1 as i32,
+        }),
+        _ => None,
+    }
+}
+
+/// Reports the buttons the pointer left and entered by moving from `old` to `new`.
+fn hover_events(old: Location, new: Location, output: &mut Vec<PointerOutput>) {
+    for event in hover_changes(old, new).into_iter().flatten() {
+        output.push(PointerOutput::Event(event));
+    }
+}
+
+fn hover_changes(old: Location, new: Location) -> [Option<DecorationEvent>; 2] {
+    match (old, new) {
+        (Location::Button(old), Location::Button(new)) if old == new => [None, None],
+        (old, new) => [
+            match old {
+                Location::Button(kind) => Some(DecorationEvent::HoverLeave(kind)),
+                _ => None,
+            },
+            match new {
+                Location::Button(kind) => Some(DecorationEvent::HoverEnter(kind)),
+                _ => None,
+            },
+        ],
+    }
+}
+
+fn change_pointer(pointer: &ThemedPointer, inner: &Inner, location: Location, serial: Option<u32>) {
+    // Prevent theming of the surface if it was requested.
+    if !inner.theme_over_surface
+        && matches!(
+            location,
+            Location::None | Location::Drag | Location::Reserved
+        )
+    {
+        return;
+    }
+
+    let name = match location {
+        // If we can't resize a frame we shouldn't show resize cursors.
+        _ if !inner.resizable => "left_ptr",
+        Location::Top => "top_side",
+        Location::TopRight => "top_right_corner",
+        Location::Right => "right_side",
+        Location::BottomRight => "bottom_right_corner",
+        Location::Bottom => "bottom_side",
+        Location::BottomLeft => "bottom_left_corner",
+        Location::Left => "left_side",
+        Location::TopLeft => "top_left_corner",
+        _ => "left_ptr",
+    };
+
+    if pointer.set_cursor(name, serial).is_err() {
+        error!("Failed to set cursor");
+    }
+}
+
+#[test]
+fn double_tap() {
+    let mut clicks = ClickTracker::default();
+    assert!(!clicks.click(Location::Head, 1000));
+    assert!(clicks.click(Location::Head, 1150));
+}
+
+#[test]
+fn double_tap_dispatched_late() {
+    // Both taps arrive at once, only their timestamps tell them apart.
+    let mut clicks = ClickTracker::default();
+    assert!(!clicks.click(Location::Head, 1000));
+    assert!(!clicks.click(Location::Head, 1600));
+}
+
+#[test]
+fn triple_tap() {
+    let mut clicks = ClickTracker::default();
+    assert!(!clicks.click(Location::Head, 1000));
+    assert!(clicks.click(Location::Head, 1100));
+    assert!(!clicks.click(Location::Head, 1200));
+}
+
+#[test]
+fn taps_on_different_locations() {
+    let mut clicks = ClickTracker::default();
+    assert!(!clicks.click(Location::Head, 1000));
+    assert!(!clicks.click(Location::Button(ButtonKind::Close), 1100));
+    assert!(!clicks.click(Location::Head, 1200));
+}
+
+#[test]
+fn tap_timestamp_wraparound() {
+    let mut clicks = ClickTracker::default();
+    assert!(!clicks.click(Location::Head, u32::MAX - 100));
+    assert!(clicks.click(Location::Head, 100));
+}
+
+#[test]
+fn drag_regions_of_the_content() {
+    let regions = [
+        LogicalRect::new(0, 0, 200, 40),
+        LogicalRect::new(300, 0, 50, 40),
+    ];
+    assert_eq!(drag_location(&regions, 10.0, 10.0), Location::Drag);
+    assert_eq!(drag_location(&regions, 320.0, 39.0), Location::Drag);
+    assert_eq!(drag_location(&regions, 250.0, 10.0), Location::None);
+    assert_eq!(drag_location(&regions, 10.0, 40.0), Location::None);
+    assert_eq!(drag_location(&[], 10.0, 10.0), Location::None);
+}
+
+#[test]
+fn hover_between_buttons() {
+    use DecorationEvent::{HoverEnter, HoverLeave};
+    let close = Location::Button(ButtonKind::Close);
+    let maximize = Location::Button(ButtonKind::Maximize);
+    assert_eq!(
+        hover_changes(Location::Head, close),
+        [None, Some(HoverEnter(ButtonKind::Close))]
+    );
+    assert_eq!(
+        hover_changes(close, maximize),
+        [
+            Some(HoverLeave(ButtonKind::Close)),
+            Some(HoverEnter(ButtonKind::Maximize))
+        ]
+    );
+    assert_eq!(hover_changes(close, close), [None, None]);
+    assert_eq!(
+        hover_changes(maximize, Location::None),
+        [Some(HoverLeave(ButtonKind::Maximize)), None]
+    );
+    assert_eq!(hover_changes(Location::Head, Location::Top), [None, None]);
+}
+
+#[test]
+fn resize_edges_of_requests() {
+    assert_eq!(
+        ResizeEdge::from(core::ResizeEdge::BottomLeft),
+        ResizeEdge::BottomLeft
+    );
+    assert_eq!(ResizeEdge::from(core::ResizeEdge::Top), ResizeEdge::Top);
+}
+
+#[test]
+fn buttons_dropped_under_the_pointer() {
+    let mut state = crate::RenderState::new((200, 100), 1);
+    let buttons = state.buttons();
+    let minimize = buttons.get(ButtonKind::Minimize);
+    let (x, y) = (minimize.center_x().into(), minimize.center_y().into());
+
+    let mut pointer = PointerState::new();
+    let mut output = Vec::new();
+    let enter = PointerInput::Enter {
+        surface: Location::Head,
+        on_content: false,
+        x,
+        y,
+    };
+    pointer.input(enter, &PointerFrame::new(&state, &buttons), &mut output);
+    assert_eq!(pointer.location, Location::Button(ButtonKind::Minimize));
+
+    // Shrunk without the pointer moving, the button isn't there to click anymore.
+    state.size.0 = 60;
+    let buttons = state.buttons();
+    output.clear();
+    let press = PointerInput::Button {
+        time: 0,
+        button: 0x110,
+        pressed: true,
+    };
+    pointer.input(press, &PointerFrame::new(&state, &buttons), &mut output);
+    assert!(
+        output.contains(&PointerOutput::Event(DecorationEvent::HoverLeave(
+            ButtonKind::Minimize
+        )))
+    );
+    assert_ne!(pointer.location, Location::Button(ButtonKind::Minimize));
+}
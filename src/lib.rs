@@ -1,34 +1,106 @@
+mod animation;
+mod builder;
 mod buttons;
+mod compose;
 mod config;
+pub mod core;
+mod dconf;
+mod error;
+mod input;
+mod logging;
+mod metrics;
 mod parts;
-mod pointer;
+mod pixel;
+mod pool;
+mod shadow;
 mod surface;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod theme;
 mod title;
+mod transform;
 
-use crate::theme::ColorMap;
-use buttons::{ButtonKind, Buttons};
+use crate::core::{
+    button_clear_paint, clear_reserved_area, draw_bottom_border, draw_button, draw_header_shadow,
+    draw_headerbar, draw_left_border, draw_right_border, hidden_borders, part_origin,
+    HeaderScratch,
+};
+use animation::{AnimationKind, Animations};
+use buttons::{ButtonIcons, Buttons};
 use client::{
-    protocol::{wl_compositor, wl_seat, wl_shm, wl_subcompositor, wl_surface},
-    Attached, DispatchData,
+    protocol::{
+        wl_compositor, wl_output, wl_pointer, wl_seat, wl_shm, wl_subcompositor, wl_surface,
+    },
+    Attached, DispatchData, GlobalManager,
+};
+use compose::{
+    clear_part, draw_result, set_header_input_region, BorderState, Composer, HeaderCache,
+    HeaderState, Placement,
 };
+use error::buffer_stride;
+use input::{PointerFrame, PointerUserData};
+use logging::{debug, warn};
 use parts::Parts;
-use pointer::PointerUserData;
+use pool::Pool;
+use shadow::Shadow;
 use smithay_client_toolkit::{
-    reexports::client,
+    reexports::{
+        client,
+        protocols::{
+            misc::server_decoration::client::{
+                org_kde_kwin_server_decoration::{
+                    self, Mode as ServerDecorationMode, OrgKdeKwinServerDecoration,
+                },
+                org_kde_kwin_server_decoration_manager::OrgKdeKwinServerDecorationManager,
+            },
+            viewporter::client::wp_viewporter::WpViewporter,
+        },
+    },
     seat::pointer::{ThemeManager, ThemeSpec, ThemedPointer},
-    shm::AutoMemPool,
     window::{Frame, FrameRequest, State, WindowState},
 };
-use std::{cell::RefCell, fmt, rc::Rc};
-use theme::{ColorTheme, BORDER_SIZE, HEADER_SIZE};
-use tiny_skia::{
-    ClipMask, Color, FillRule, Paint, Path, PathBuilder, Pixmap, PixmapMut, PixmapPaint, Point,
-    Rect, Transform,
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt,
+    rc::Rc,
+    time::{Duration, Instant},
 };
+use theme::{ColorTheme, Sizes, BORDER_SIZE, CORNER_RADIUS, HEADER_SIZE};
+use tiny_skia::{Color, IntRect, Pixmap, PixmapMut, Rect, Transform};
 use title::TitleText;
 
+pub use crate::core::{
+    draw_decorations, hit_test, render_decorations, sanitize_size, DecorationHit, Edges,
+    HeaderMode, LogicalRect, PointerRequest, RenderState, MAX_CONTENT_SIZE, MIN_CONTENT_SIZE,
+};
+pub use builder::{AdwaitaConfigBuilder, ConfigError};
+pub use buttons::ButtonKind;
+pub use config::{
+    DconfSettings, EnvSettings, GsettingsSettings, PortalSettings, Resolved, SettingsProvider,
+    SettingsSource, SystemSettings,
+};
+pub use error::DecorationError;
+pub use metrics::{DecorationPart, RedrawMetrics, SurfaceDamage};
+pub use pixel::PixelFormat;
+
 type SkiaResult = Option<()>;
+type FrameCallback = Box<dyn FnMut(FrameRequest, u32, DispatchData)>;
+
+/// Callback invoked when the window should be maximized along a single axis.
+pub type AxisMaximizeCallback = Rc<dyn Fn(MaximizeAxis)>;
+
+/// Callback invoked for scroll events over the titlebar.
+pub type TitlebarAxisCallback = Rc<dyn Fn(wl_pointer::Axis, f64)>;
+
+/// Callback invoked after every redraw with what it cost.
+pub type RedrawMetricsCallback = Rc<dyn Fn(&RedrawMetrics)>;
+
+/// Callback invoked when a redraw of the decorations failed.
+pub type RedrawErrorCallback = Rc<dyn Fn(&DecorationError)>;
+
+/// Callback invoked for every interaction with the decorations.
+pub type DecorationEventCallback = Rc<dyn Fn(&DecorationEvent)>;
 
 /*
  * Utilities
@@ -47,21 +119,147 @@ enum Location {
     Left,
     TopLeft,
     Button(ButtonKind),
+    /// A drag region of the content.
+    Drag,
+    /// The part of the headerbar reserved for the app.
+    Reserved,
 }
 
 /*
  * The core frame
  */
 
+/// A call into the embedder, queued while [`Inner`] is borrowed.
+#[derive(Debug)]
+enum Outgoing {
+    Request(FrameRequest, u32),
+    Event(DecorationEvent),
+    AxisMaximize(MaximizeAxis),
+    TitlebarAxis(wl_pointer::Axis, f64),
+}
+
 struct Inner {
     parts: Parts,
     size: (u32, u32),
     resizable: bool,
     theme_over_surface: bool,
-    implem: Box<dyn FnMut(FrameRequest, u32, DispatchData)>,
+    /// Taken out while it is being called.
+    implem: Option<FrameCallback>,
+    /// Delivered by [`dispatch`] once `Inner` isn't borrowed anymore.
+    outgoing: VecDeque<Outgoing>,
+    axis_maximize: Option<AxisMaximizeCallback>,
+    titlebar_axis: Option<TitlebarAxisCallback>,
+    redraw_metrics: Option<RedrawMetricsCallback>,
+    redraw_error: Option<RedrawErrorCallback>,
+    decoration_events: Option<DecorationEventCallback>,
+    button_scroll: ButtonScroll,
     maximized: bool,
     fullscreened: bool,
-    tiled: bool,
+    /// The compositor is resizing the window.
+    resizing: bool,
+    /// KWin draws server-side decorations around the window.
+    server_decorated: bool,
+    tiled: Edges,
+    header_mode: HeaderMode,
+    /// The window content, whose drag regions the pointers also track.
+    content: wl_surface::WlSurface,
+    drag_regions: Vec<LogicalRect>,
+    reserved_area: Option<LogicalRect>,
+    /// Something shown by the decorations changed since the last redraw.
+    dirty: bool,
+}
+
+impl Inner {
+    /// Edges whose resize borders are currently hidden.
+    fn hidden_borders(&self) -> Edges {
+        if self.fullscreened {
+            Edges::ALL
+        } else {
+            hidden_borders(self.maximized, self.tiled)
+        }
+    }
+
+    /// Whether the window is shown without these decorations, being fullscreen or decorated by
+    /// the compositor.
+    fn undecorated(&self) -> bool {
+        self.fullscreened || self.server_decorated
+    }
+
+    /// What the pointer handlers act on.
+    fn pointer_frame<'a>(&'a self, buttons: &'a Buttons) -> PointerFrame<'a> {
+        PointerFrame {
+            buttons,
+            width: self.size.0,
+            hidden: self.hidden_borders(),
+            header_mode: self.header_mode,
+            resizable: self.resizable,
+            maximized: self.maximized,
+            axis_maximize: self.axis_maximize.is_some(),
+            titlebar_axis: self.titlebar_axis.is_some(),
+            button_scroll: self.button_scroll,
+            drag_regions: &self.drag_regions,
+            reserved_area: self.reserved_area,
+        }
+    }
+
+    fn request(&mut self, request: FrameRequest, serial: u32) {
+        self.outgoing.push_back(Outgoing::Request(request, serial));
+    }
+
+    fn send_event(&mut self, event: DecorationEvent) {
+        if self.decoration_events.is_some() {
+            self.outgoing.push_back(Outgoing::Event(event));
+        }
+    }
+}
+
+/// Delivers the queued calls into the embedder.
+///
+/// `inner` is only borrowed in between them, so the embedder can call back into the frame,
+/// e.g. to change its config from a decoration event.
+fn dispatch(inner: &RefCell<Inner>, mut ddata: DispatchData) {
+    loop {
+        let mut guard = inner.borrow_mut();
+        let Some(outgoing) = guard.outgoing.pop_front() else {
+            return;
+        };
+
+        match outgoing {
+            Outgoing::Request(request, serial) => {
+                let Some(mut implem) = guard.implem.take() else {
+                    // Called from within `implem`, the outer dispatch delivers it.
+                    guard
+                        .outgoing
+                        .push_front(Outgoing::Request(request, serial));
+                    return;
+                };
+                drop(guard);
+                implem(request, serial, ddata.reborrow());
+                inner.borrow_mut().implem = Some(implem);
+            }
+            Outgoing::Event(event) => {
+                let callback = guard.decoration_events.clone();
+                drop(guard);
+                if let Some(callback) = callback {
+                    callback(&event);
+                }
+            }
+            Outgoing::AxisMaximize(axis) => {
+                let callback = guard.axis_maximize.clone();
+                drop(guard);
+                if let Some(callback) = callback {
+                    callback(axis);
+                }
+            }
+            Outgoing::TitlebarAxis(axis, value) => {
+                let callback = guard.titlebar_axis.clone();
+                drop(guard);
+                if let Some(callback) = callback {
+                    callback(axis, value);
+                }
+            }
+        }
+    }
 }
 
 impl fmt::Debug for Inner {
@@ -73,103 +271,491 @@ impl fmt::Debug for Inner {
             .field("theme_over_surface", &self.theme_over_surface)
             .field(
                 "implem",
-                &"FnMut(FrameRequest, u32, DispatchData) -> { ... }",
+                &self
+                    .implem
+                    .as_ref()
+                    .map(|_| "FnMut(FrameRequest, u32, DispatchData) -> { ... }"),
+            )
+            .field("outgoing", &self.outgoing)
+            .field(
+                "axis_maximize",
+                &self
+                    .axis_maximize
+                    .as_ref()
+                    .map(|_| "Fn(MaximizeAxis) -> { ... }"),
+            )
+            .field(
+                "titlebar_axis",
+                &self
+                    .titlebar_axis
+                    .as_ref()
+                    .map(|_| "Fn(Axis, f64) -> { ... }"),
+            )
+            .field(
+                "redraw_metrics",
+                &self
+                    .redraw_metrics
+                    .as_ref()
+                    .map(|_| "Fn(&RedrawMetrics) -> { ... }"),
             )
+            .field(
+                "redraw_error",
+                &self
+                    .redraw_error
+                    .as_ref()
+                    .map(|_| "Fn(&DecorationError) -> { ... }"),
+            )
+            .field(
+                "decoration_events",
+                &self
+                    .decoration_events
+                    .as_ref()
+                    .map(|_| "Fn(&DecorationEvent) -> { ... }"),
+            )
+            .field("button_scroll", &self.button_scroll)
             .field("maximized", &self.maximized)
             .field("fullscreened", &self.fullscreened)
+            .field("server_decorated", &self.server_decorated)
+            .field("header_mode", &self.header_mode)
+            .field("drag_regions", &self.drag_regions)
+            .field("reserved_area", &self.reserved_area)
+            .field("dirty", &self.dirty)
             .finish()
     }
 }
 
-fn precise_location(buttons: &Buttons, old: Location, width: u32, x: f64, y: f64) -> Location {
-    match old {
-        Location::Head
-        | Location::Button(_)
-        | Location::Top
-        | Location::TopLeft
-        | Location::TopRight => match buttons.find_button(x, y) {
-            Location::Head => {
-                if y <= f64::from(BORDER_SIZE) {
-                    if x <= f64::from(BORDER_SIZE) {
-                        Location::TopLeft
-                    } else if x >= f64::from(width + BORDER_SIZE) {
-                        Location::TopRight
-                    } else {
-                        Location::Top
-                    }
-                } else if x < f64::from(BORDER_SIZE) {
-                    Location::TopLeft
-                } else if x > f64::from(width) {
-                    Location::TopRight
-                } else {
-                    Location::Head
-                }
-            }
-            other => other,
-        },
+/// Returns `true` if `surface` is one of the decoration surfaces of an [`AdwaitaFrame`].
+///
+/// `wl_data_device` drag-and-drop events are delivered to the embedder, and a drag entering the
+/// titlebar or borders reports the decoration subsurface instead of the window surface. This can
+/// be used to react to such drags, e.g. to raise the window while something is dragged over its
+/// title.
+///
+/// To tell which window a drag entered, forward the enter to [`AdwaitaFrame::dnd_enter`] and
+/// handle [`DecorationEvent::DndEnter`] instead.
+pub fn is_decoration_surface(surface: &wl_surface::WlSurface) -> bool {
+    surface::is_decoration_surface(surface)
+}
 
-        Location::Bottom | Location::BottomLeft | Location::BottomRight => {
-            if x <= f64::from(BORDER_SIZE) {
-                Location::BottomLeft
-            } else if x >= f64::from(width + BORDER_SIZE) {
-                Location::BottomRight
-            } else {
-                Location::Bottom
-            }
-        }
+/// Axis of a single-axis maximize request.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MaximizeAxis {
+    /// Requested by double-clicking the left or right border.
+    Horizontal,
+    /// Requested by double-clicking the top or bottom border.
+    Vertical,
+}
 
-        other => other,
-    }
+/// Interaction with the decorations, reported to [`AdwaitaConfig::decoration_events`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DecorationEvent {
+    /// The pointer moved onto a titlebar button.
+    HoverEnter(ButtonKind),
+    /// The pointer left a titlebar button.
+    HoverLeave(ButtonKind),
+    /// The left mouse button was pressed on a titlebar button.
+    ButtonPressed(ButtonKind),
+    /// The left mouse button was released after [`DecorationEvent::ButtonPressed`], the button
+    /// only acts if the pointer is still over it.
+    ButtonReleased(ButtonKind),
+    /// A move of the window was requested by dragging the titlebar.
+    DragStarted,
+    /// The move started by [`DecorationEvent::DragStarted`] ended.
+    ///
+    /// The compositor doesn't announce this, it's reported with the first pointer event the
+    /// window gets once the compositor released the pointer.
+    DragEnded,
+    /// A resize of the window was requested by dragging one of its edges.
+    ResizeStarted(crate::core::ResizeEdge),
+    /// The compositor stopped resizing the window, also after resizes it started itself.
+    ResizeEnded,
+    /// The window menu was requested at a position relative to the window content.
+    MenuRequested { x: i32, y: i32 },
+    /// A drag-and-drop entered a decoration surface, e.g. to raise the window while something is
+    /// dragged over its title. Reported by [`AdwaitaFrame::dnd_enter`].
+    DndEnter(DecorationPart),
+    /// The drag-and-drop of [`DecorationEvent::DndEnter`] left the decoration surface, or was
+    /// dropped on it. Reported by [`AdwaitaFrame::dnd_leave`].
+    DndLeave,
 }
 
-#[derive(Debug, Clone)]
-pub struct FrameConfig {
+/// Handling of scroll events over the titlebar buttons.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ButtonScroll {
+    /// Scrolling over a button is ignored.
+    #[default]
+    Consume,
+    /// Scrolling over a button is reported like scrolling over the rest of the titlebar.
+    Forward,
+}
+
+/// Extents of the decorations on each side of the window content, in logical pixels.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct DecorationInsets {
+    pub top: u32,
+    pub left: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+/// Former name of [`AdwaitaConfig`].
+pub type FrameConfig = AdwaitaConfig;
+
+/// Configuration of an [`AdwaitaFrame`], applied with `Window::set_frame_config`.
+#[derive(Clone)]
+pub struct AdwaitaConfig {
+    /// Colors shared by all windows, e.g. detected once with [`ColorTheme::auto`].
     pub theme: ColorTheme,
+    /// Colors of this window only, taking precedence over `theme`.
+    ///
+    /// Lets a window keep its own look within a configuration shared by all windows, like a
+    /// media player with a dark titlebar regardless of the system preference.
+    pub theme_override: Option<ColorTheme>,
+    /// Buttons of the titlebar from right to left, buttons left out aren't shown.
+    pub button_layout: Vec<ButtonKind>,
+    /// Radius of the rounded header corners in logical pixels.
+    pub corner_radius: f32,
+    /// Font of the title like `Cantarell Bold 11`, if unset the GNOME titlebar font is used.
+    pub title_font: Option<String>,
+    /// Height of the header in logical pixels, at least as high as the buttons.
+    pub header_height: u32,
+    /// Width of the borders in logical pixels, which is the size of the resize margins.
+    pub border_size: u32,
+    /// Whether the header is a full headerbar, or only buttons floating over the content.
+    pub header_mode: HeaderMode,
+    /// Areas of the content, in content coordinates, that move the window like the titlebar.
+    ///
+    /// Meant for headerbars drawn by the app into its own surface. Pressing them also starts a
+    /// move, maximizes on double-click and opens the window menu on right click. The app still
+    /// receives these events on its own pointer and should ignore them.
+    pub drag_regions: Vec<LogicalRect>,
+    /// Part of the headerbar left to the app, relative to the top left corner of the headerbar.
+    ///
+    /// Nothing is drawn there, e.g. for a search entry the app draws in a subsurface above
+    /// the header, and the frame ignores pointer events over it. The app receives them on its
+    /// own pointer, over the header surface, see [`AdwaitaFrame::decoration_part`].
+    pub reserved_area: Option<LogicalRect>,
+    /// Called on double-click on a border.
+    ///
+    /// `xdg_toplevel` has no notion of maximizing along one axis, so this is left to the
+    /// embedder. If unset, double-clicking a border starts a resize like a single click.
+    pub axis_maximize: Option<AxisMaximizeCallback>,
+    /// Called on scroll over the titlebar.
+    pub titlebar_axis: Option<TitlebarAxisCallback>,
+    /// Whether scrolling over the buttons reaches `titlebar_axis`.
+    pub button_scroll: ButtonScroll,
+    /// Called after every redraw, e.g. to show the cost of the decorations in a profiler.
+    pub redraw_metrics: Option<RedrawMetricsCallback>,
+    /// Called when a redraw failed, the failed surfaces are redrawn by the next redraw.
+    ///
+    /// After a [`DecorationError::Shm`] that redraw uses a new shm pool. Failures are logged
+    /// either way.
+    ///
+    /// Frames without a `wl_subcompositor` call it when they are configured, they never draw.
+    pub redraw_error: Option<RedrawErrorCallback>,
+    /// Called for hovers, clicks, drags and menu requests on the decorations, before the
+    /// resulting request reaches the window.
+    pub decoration_events: Option<DecorationEventCallback>,
+    /// Bound `wp_viewporter` global, used to shrink the border buffers.
+    pub viewporter: Option<Attached<WpViewporter>>,
+    /// Bound `org_kde_kwin_server_decoration_manager` global, for KWin versions without
+    /// `zxdg_decoration_manager_v1`.
+    ///
+    /// The frame hides itself while KWin decorates the window and requests a refresh, the window
+    /// geometry follows with the next `Window::resize`. Using both protocols for one window is
+    /// undefined, so only pass it in if `zxdg_decoration_manager_v1` is missing.
+    pub kde_server_decoration: Option<Attached<OrgKdeKwinServerDecorationManager>>,
+    /// Preferred format of the decoration buffers.
+    pub pixel_format: PixelFormat,
+    /// Formats advertised by the compositor through `wl_shm.format`, e.g. from
+    /// `Environment::shm_formats`.
+    ///
+    /// The frame has no access to the events of the bound `wl_shm`, so without them
+    /// `pixel_format` is used as is and has to be supported by the compositor.
+    pub shm_formats: Option<Vec<wl_shm::Format>>,
+    /// Scale to draw the decorations at, instead of the one of the outputs they are on, for
+    /// embedders managing the scale themselves.
+    ///
+    /// Buffer scales are integers, fractional scales are rounded up and left to the compositor
+    /// to scale down.
+    pub scale_factor: Option<f64>,
+    /// Whether the header shape and the buttons are drawn anti-aliased, if not, their geometry
+    /// is snapped to whole pixels for crisp edges.
+    pub anti_alias: bool,
+    /// Whether a soft shadow is drawn around floating, focused windows.
+    pub shadows: bool,
+    /// Whether the window is suspended, e.g. minimized or fully occluded, like the `suspended`
+    /// state of `xdg_toplevel` version 6.
+    ///
+    /// sctk drops states it doesn't know, so this is left to the embedder. Nothing is drawn or
+    /// animated while suspended, clearing it redraws the decorations.
+    pub suspended: bool,
+    /// Whether the decorations fade in when they are shown again, e.g. after leaving fullscreen.
+    ///
+    /// Left out if the system prefers reduced motion. Hidden decorations leave the window
+    /// geometry right away, so there is nothing to fade out.
+    pub animate_visibility: bool,
+    /// Resources shared with other frames.
+    pub shell: Option<AdwaitaShell>,
 }
 
-impl FrameConfig {
+impl fmt::Debug for AdwaitaConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdwaitaConfig")
+            .field("theme", &self.theme)
+            .field("theme_override", &self.theme_override)
+            .field("button_layout", &self.button_layout)
+            .field("corner_radius", &self.corner_radius)
+            .field("title_font", &self.title_font)
+            .field("header_height", &self.header_height)
+            .field("border_size", &self.border_size)
+            .field("header_mode", &self.header_mode)
+            .field("drag_regions", &self.drag_regions)
+            .field("reserved_area", &self.reserved_area)
+            .field(
+                "axis_maximize",
+                &self
+                    .axis_maximize
+                    .as_ref()
+                    .map(|_| "Fn(MaximizeAxis) -> { ... }"),
+            )
+            .field(
+                "titlebar_axis",
+                &self
+                    .titlebar_axis
+                    .as_ref()
+                    .map(|_| "Fn(Axis, f64) -> { ... }"),
+            )
+            .field("button_scroll", &self.button_scroll)
+            .field(
+                "redraw_metrics",
+                &self
+                    .redraw_metrics
+                    .as_ref()
+                    .map(|_| "Fn(&RedrawMetrics) -> { ... }"),
+            )
+            .field(
+                "redraw_error",
+                &self
+                    .redraw_error
+                    .as_ref()
+                    .map(|_| "Fn(&DecorationError) -> { ... }"),
+            )
+            .field(
+                "decoration_events",
+                &self
+                    .decoration_events
+                    .as_ref()
+                    .map(|_| "Fn(&DecorationEvent) -> { ... }"),
+            )
+            .field("viewporter", &self.viewporter)
+            .field("kde_server_decoration", &self.kde_server_decoration)
+            .field("pixel_format", &self.pixel_format)
+            .field("shm_formats", &self.shm_formats)
+            .field("scale_factor", &self.scale_factor)
+            .field("anti_alias", &self.anti_alias)
+            .field("shadows", &self.shadows)
+            .field("suspended", &self.suspended)
+            .field("animate_visibility", &self.animate_visibility)
+            .field("shell", &self.shell)
+            .finish()
+    }
+}
+
+impl AdwaitaConfig {
     pub fn auto() -> Self {
-        Self {
-            theme: ColorTheme::auto(),
-        }
+        Self::with_theme(ColorTheme::auto())
     }
 
     pub fn light() -> Self {
-        Self {
-            theme: ColorTheme::light(),
-        }
+        Self::with_theme(ColorTheme::light())
     }
 
     pub fn dark() -> Self {
+        Self::with_theme(ColorTheme::dark())
+    }
+
+    /// Starts from the `auto` configuration, with its values checked by
+    /// [`AdwaitaConfigBuilder::build`].
+    pub fn builder() -> AdwaitaConfigBuilder {
+        AdwaitaConfigBuilder::new(ColorTheme::auto())
+    }
+
+    pub(crate) fn with_theme(theme: ColorTheme) -> Self {
         Self {
-            theme: ColorTheme::dark(),
+            theme,
+            theme_override: None,
+            button_layout: ButtonKind::ALL.to_vec(),
+            corner_radius: CORNER_RADIUS,
+            title_font: None,
+            header_height: HEADER_SIZE,
+            border_size: BORDER_SIZE,
+            header_mode: HeaderMode::Full,
+            drag_regions: Vec::new(),
+            reserved_area: None,
+            axis_maximize: None,
+            titlebar_axis: None,
+            button_scroll: ButtonScroll::default(),
+            redraw_metrics: None,
+            redraw_error: None,
+            decoration_events: None,
+            viewporter: None,
+            kde_server_decoration: None,
+            pixel_format: PixelFormat::default(),
+            shm_formats: None,
+            scale_factor: None,
+            anti_alias: true,
+            shadows: true,
+            suspended: false,
+            animate_visibility: false,
+            shell: None,
         }
     }
 }
 
+/// Resources shared by the frames of all windows of an application.
+///
+/// Windows are created with `shell.theme_manager()` to share cursor themes, and configured with
+/// `shell.config()` to share one shm pool. Button icons and title fonts are shared by all
+/// frames anyway.
+///
+/// The system settings are read once for all windows, when the shell is created.
+#[derive(Debug, Clone)]
+pub struct AdwaitaShell {
+    theme_manager: ThemeManager,
+    pool: Rc<RefCell<Pool>>,
+    theme: ColorTheme,
+    settings: SystemSettings,
+}
+
+impl AdwaitaShell {
+    pub fn new(
+        compositor: &Attached<wl_compositor::WlCompositor>,
+        shm: &Attached<wl_shm::WlShm>,
+    ) -> Result<Self, DecorationError> {
+        Self::with_settings(compositor, shm, SystemSettings::read())
+    }
+
+    /// Follows `settings` rather than reading the system settings, e.g. from
+    /// [`SystemSettings::read_with`] to add the embedder's own.
+    pub fn with_settings(
+        compositor: &Attached<wl_compositor::WlCompositor>,
+        shm: &Attached<wl_shm::WlShm>,
+        settings: SystemSettings,
+    ) -> Result<Self, DecorationError> {
+        Ok(Self {
+            theme_manager: ThemeManager::init(ThemeSpec::System, compositor.clone(), shm.clone()),
+            pool: Rc::new(RefCell::new(Pool::new(shm.clone())?)),
+            theme: match settings.prefer_dark.value {
+                true => ColorTheme::dark(),
+                false => ColorTheme::light(),
+            },
+            settings,
+        })
+    }
+
+    /// Binds `wl_compositor` and `wl_shm` from the globals advertised by the compositor.
+    pub fn from_globals(globals: &GlobalManager) -> Result<Self, DecorationError> {
+        let compositor = globals
+            .instantiate_range::<wl_compositor::WlCompositor>(1, 4)
+            .map_err(|_| DecorationError::MissingGlobal("wl_compositor"))?;
+        let shm = globals
+            .instantiate_exact::<wl_shm::WlShm>(1)
+            .map_err(|_| DecorationError::MissingGlobal("wl_shm"))?;
+        Self::new(&compositor, &shm)
+    }
+
+    /// The settings the windows follow.
+    pub fn settings(&self) -> &SystemSettings {
+        &self.settings
+    }
+
+    /// Cursor themes to create windows with.
+    pub fn theme_manager(&self) -> ThemeManager {
+        self.theme_manager.clone()
+    }
+
+    /// Configuration of a window sharing the resources of this shell, in the theme and button
+    /// layout of its settings.
+    pub fn config(&self) -> AdwaitaConfig {
+        AdwaitaConfig {
+            shell: Some(self.clone()),
+            button_layout: self.button_layout(),
+            ..AdwaitaConfig::with_theme(self.theme.clone())
+        }
+    }
+
+    /// Starts from [`config`](Self::config), see [`AdwaitaConfig::builder`].
+    pub fn builder(&self) -> AdwaitaConfigBuilder {
+        AdwaitaConfigBuilder::new(self.theme.clone())
+            .shell(self.clone())
+            .button_layout(self.button_layout())
+    }
+
+    fn button_layout(&self) -> Vec<ButtonKind> {
+        self.settings
+            .button_layout
+            .value
+            .clone()
+            .unwrap_or_else(|| ButtonKind::ALL.to_vec())
+    }
+}
+
 /// A simple set of decorations
+///
+/// Windows created with `Decorations::FollowServer` hide the frame while the compositor draws
+/// server-side decorations through `zxdg_decoration_manager_v1`, releasing its surfaces and
+/// buffers and reporting no [`insets`](AdwaitaFrame::insets), and show it again if the compositor
+/// switches back to client-side decorations.
+///
+/// Without a `wl_subcompositor` there is nowhere to draw, the frame stays hidden and reports a
+/// [`DecorationError::MissingGlobal`] to the [`redraw_error`](AdwaitaConfig::redraw_error)
+/// callback.
 #[derive(Debug)]
 pub struct AdwaitaFrame {
     base_surface: wl_surface::WlSurface,
     compositor: Attached<wl_compositor::WlCompositor>,
     subcompositor: Attached<wl_subcompositor::WlSubcompositor>,
+    /// Whether `subcompositor` is a placeholder for a global the compositor doesn't have.
+    subcompositor_missing: bool,
     inner: Rc<RefCell<Inner>>,
-    pool: AutoMemPool,
+    pool: Rc<RefCell<Pool>>,
     active: WindowState,
     hidden: bool,
+    /// Nothing is drawn while suspended.
+    suspended: bool,
+    /// Whether decorations fade in when they are shown.
+    animate_visibility: bool,
     pointers: Vec<ThemedPointer>,
     themer: ThemeManager,
+    server_decoration: Option<OrgKdeKwinServerDecoration>,
+    /// Whether a drag forwarded with `dnd_enter` is over the decorations.
+    dnd_entered: bool,
     surface_version: u32,
+    pixel_format: PixelFormat,
+    anti_alias: bool,
+    shadows: bool,
+    corner_radius: f32,
 
     buttons: Rc<RefCell<Buttons>>,
+    button_icons: ButtonIcons,
     colors: ColorTheme,
     title: Option<String>,
+    title_font: Option<String>,
     title_text: Option<TitleText>,
+    header_cache: Option<HeaderCache>,
+    /// Whether the header shows the plain fallback, at buffer scale 1.
+    header_fallback: bool,
+    border_state: Option<BorderState>,
+    animations: Animations<AnimationKind>,
+    /// Cost of the ongoing redraw.
+    redraw_metrics: RedrawMetrics,
+    header_scratch: HeaderScratch,
 }
 
 impl Frame for AdwaitaFrame {
-    type Error = ::std::io::Error;
-    type Config = FrameConfig;
+    type Error = DecorationError;
+    type Config = AdwaitaConfig;
     fn init(
         base_surface: &wl_surface::WlSurface,
         compositor: &Attached<wl_compositor::WlCompositor>,
@@ -177,7 +763,7 @@ impl Frame for AdwaitaFrame {
         shm: &Attached<wl_shm::WlShm>,
         theme_manager: Option<ThemeManager>,
         implementation: Box<dyn FnMut(FrameRequest, u32, DispatchData)>,
-    ) -> Result<AdwaitaFrame, ::std::io::Error> {
+    ) -> Result<AdwaitaFrame, DecorationError> {
         let (themer, theme_over_surface) = if let Some(theme_manager) = theme_manager {
             (theme_manager, false)
         } else {
@@ -191,31 +777,67 @@ impl Frame for AdwaitaFrame {
             parts: Parts::default(),
             size: (1, 1),
             resizable: true,
-            implem: implementation,
+            implem: Some(implementation),
+            outgoing: VecDeque::new(),
             theme_over_surface,
+            axis_maximize: None,
+            titlebar_axis: None,
+            redraw_metrics: None,
+            redraw_error: None,
+            decoration_events: None,
+            button_scroll: ButtonScroll::default(),
             maximized: false,
             fullscreened: false,
-            tiled: false,
+            resizing: false,
+            server_decorated: false,
+            tiled: Edges::NONE,
+            header_mode: HeaderMode::Full,
+            content: base_surface.clone(),
+            drag_regions: Vec::new(),
+            reserved_area: None,
+            dirty: true,
         }));
 
-        let pool = AutoMemPool::new(shm.clone())?;
+        let pool = Rc::new(RefCell::new(Pool::new(shm.clone())?));
 
         let colors = ColorTheme::auto();
 
+        let subcompositor_missing = !subcompositor.as_ref().is_alive();
+        if subcompositor_missing {
+            warn!("No wl_subcompositor, the window is left without decorations");
+        }
+
         Ok(AdwaitaFrame {
             base_surface: base_surface.clone(),
             compositor: compositor.clone(),
             subcompositor: subcompositor.clone(),
+            subcompositor_missing,
             inner,
             pool,
             active: WindowState::Inactive,
             hidden: true,
+            suspended: false,
+            animate_visibility: false,
             pointers: Vec::new(),
             themer,
+            server_decoration: None,
+            dnd_entered: false,
             surface_version: compositor.as_ref().version(),
+            pixel_format: PixelFormat::Argb8888,
+            anti_alias: true,
+            shadows: true,
+            corner_radius: CORNER_RADIUS,
             buttons: Default::default(),
+            button_icons: ButtonIcons::shared(),
             title: None,
-            title_text: TitleText::new(colors.active.font_color),
+            title_font: None,
+            title_text: TitleText::new(colors.active.font_color, None),
+            header_cache: None,
+            header_fallback: false,
+            border_state: None,
+            animations: Animations::default(),
+            redraw_metrics: RedrawMetrics::default(),
+            header_scratch: HeaderScratch::default(),
             colors,
         })
     }
@@ -232,9 +854,14 @@ impl Frame for AdwaitaFrame {
                     .user_data()
                     .get::<RefCell<PointerUserData>>()
                 {
-                    let mut data = data.borrow_mut();
-                    let mut inner = inner.borrow_mut();
-                    data.event(event, &mut inner, &buttons.borrow(), &pointer, ddata);
+                    data.borrow_mut().event(
+                        event,
+                        &mut inner.borrow_mut(),
+                        &buttons.borrow(),
+                        &pointer,
+                    );
+                    // Nothing is borrowed anymore, e.g. for callbacks removing the seat.
+                    dispatch(&inner, ddata);
                 }
             },
         );
@@ -274,7 +901,15 @@ impl Frame for AdwaitaFrame {
         } else {
             WindowState::Inactive
         };
-        need_redraw |= new_active != self.active;
+        // A suspended window isn't seen fading, it just shows the new colors once resumed.
+        if new_active != self.active && !self.suspended {
+            need_redraw = true;
+            self.animations.start(
+                AnimationKind::Crossfade,
+                Duration::from_millis(200),
+                Instant::now(),
+            );
+        }
         self.active = new_active;
 
         // Process maximized.
@@ -282,24 +917,57 @@ impl Frame for AdwaitaFrame {
         need_redraw |= new_maximized != inner.maximized;
         inner.maximized = new_maximized;
 
+        let new_tiled = Edges {
+            top: states.contains(&State::TiledTop),
+            left: states.contains(&State::TiledLeft),
+            right: states.contains(&State::TiledRight),
+            bottom: states.contains(&State::TiledBottom),
+        };
+        need_redraw |= new_tiled != inner.tiled;
+        inner.tiled = new_tiled;
+
         // Process fullscreened.
         let new_fullscreened = states.contains(&State::Fullscreen);
-        need_redraw |= new_fullscreened != inner.fullscreened;
-        inner.fullscreened = new_fullscreened;
 
-        let new_tiled = states.contains(&State::TiledLeft)
-            || states.contains(&State::TiledRight)
-            || states.contains(&State::TiledTop)
-            || states.contains(&State::TiledBottom);
-        need_redraw |= new_tiled != inner.tiled;
-        inner.tiled = new_tiled;
+        // Nothing is drawn while hidden or fullscreened, the redraw showing the decorations
+        // again picks up the other changes.
+        if self.hidden || (inner.fullscreened && new_fullscreened) {
+            need_redraw = false;
+        }
+        need_redraw |= !self.hidden && new_fullscreened != inner.fullscreened;
+        let left_fullscreen = inner.fullscreened && !new_fullscreened;
+        inner.fullscreened = new_fullscreened;
+        inner.dirty |= need_redraw;
+
+        let new_resizing = states.contains(&State::Resizing);
+        let resize_ended = inner.resizing && !new_resizing;
+        inner.resizing = new_resizing;
+        // Nothing else is queued outside of the pointer and scale callbacks, so the event is
+        // delivered right away.
+        let decoration_events = inner.decoration_events.clone().filter(|_| resize_ended);
+        drop(inner);
+        if let Some(callback) = decoration_events {
+            callback(&DecorationEvent::ResizeEnded);
+        }
+        if left_fullscreen {
+            self.start_appear();
+        }
 
+        // `redraw` compares the header and border states with the last drawn ones, and only
+        // updates the surfaces which are affected by the change.
         need_redraw
     }
 
     fn set_hidden(&mut self, hidden: bool) {
+        let hidden = hidden || self.subcompositor_missing;
+        if self.hidden && !hidden {
+            self.start_appear();
+        }
         self.hidden = hidden;
+        self.invalidate_header();
+        self.border_state = None;
         let mut inner = self.inner.borrow_mut();
+        inner.dirty = true;
         if !self.hidden {
             inner.parts.add_decorations(
                 &self.base_surface,
@@ -308,70 +976,457 @@ impl Frame for AdwaitaFrame {
                 self.inner.clone(),
             );
         } else {
+            // Also hidden while `zxdg_toplevel_decoration_v1` grants server-side decorations,
+            // which can last for the lifetime of the window, so nothing is kept around.
             inner.parts.remove_decorations();
+            self.animations.clear();
+            self.header_cache = None;
+            self.pool
+                .borrow_mut()
+                .trim(Rc::as_ptr(&self.inner) as usize, 0);
         }
     }
 
     fn set_resizable(&mut self, resizable: bool) {
-        self.inner.borrow_mut().resizable = resizable;
+        let mut inner = self.inner.borrow_mut();
+        inner.dirty |= inner.resizable != resizable;
+        inner.resizable = resizable;
     }
 
     fn resize(&mut self, newsize: (u32, u32)) {
-        self.inner.borrow_mut().size = newsize;
-        self.buttons
-            .borrow_mut()
-            .arrange(newsize.0 + BORDER_SIZE * 2);
+        let newsize = sanitize_size(newsize);
+        let mut inner = self.inner.borrow_mut();
+        inner.dirty |= inner.size != newsize;
+        inner.size = newsize;
+        drop(inner);
+        let mut buttons = self.buttons.borrow_mut();
+        let border = buttons.sizes().border;
+        buttons.arrange(newsize.0 + border * 2);
     }
 
     fn redraw(&mut self) {
-        self.redraw_inner();
+        if self.suspended {
+            return;
+        }
+
+        let start = Instant::now();
+        self.redraw_metrics.reset();
+        let result = self.redraw_inner();
+        // Failed surfaces are drawn again by the next redraw.
+        self.inner.borrow_mut().dirty = result.is_err();
+        if let Err(err) = result {
+            warn!("Failed to redraw the decorations: {}", err);
+            // Surfaces may be left with outdated buffers, draw everything again next time.
+            self.invalidate_header();
+            self.border_state = None;
+            if let DecorationError::Shm(_) = err {
+                self.pool.borrow_mut().failed();
+            }
+
+            let callback = self.inner.borrow().redraw_error.clone();
+            if let Some(callback) = callback {
+                callback(&err);
+            }
+        }
+
+        self.redraw_metrics.duration = start.elapsed();
+        if self.redraw_metrics.buffers > 0 {
+            debug!(
+                "Drew {} decoration buffers of {} bytes in {:?}",
+                self.redraw_metrics.buffers,
+                self.redraw_metrics.bytes_uploaded,
+                self.redraw_metrics.duration
+            );
+        }
+
+        let callback = self.inner.borrow().redraw_metrics.clone();
+        if let Some(callback) = callback {
+            callback(&self.redraw_metrics);
+        }
     }
 
     fn subtract_borders(&self, width: i32, height: i32) -> (i32, i32) {
-        if self.hidden || self.inner.borrow().fullscreened {
+        if self.hidden || self.inner.borrow().undecorated() {
             (width, height)
         } else {
-            (width, height - HEADER_SIZE as i32)
+            // sctk clamps to the minimum size, which may be zero, and casts to `u32`.
+            (width.max(0), (height - self.header_height() as i32).max(0))
         }
     }
 
     fn add_borders(&self, width: i32, height: i32) -> (i32, i32) {
-        if self.hidden || self.inner.borrow().fullscreened {
+        if self.hidden || self.inner.borrow().undecorated() {
             (width, height)
         } else {
-            (width, height + HEADER_SIZE as i32)
+            (width, height + self.header_height() as i32)
         }
     }
 
     fn location(&self) -> (i32, i32) {
-        if self.hidden || self.inner.borrow().fullscreened {
+        if self.hidden || self.inner.borrow().undecorated() {
             (0, 0)
         } else {
-            (0, -(HEADER_SIZE as i32))
+            (0, -(self.header_height() as i32))
         }
     }
 
-    fn set_config(&mut self, config: FrameConfig) {
-        self.colors = config.theme;
+    fn set_config(&mut self, config: AdwaitaConfig) {
+        self.colors = config.theme_override.unwrap_or(config.theme);
+        self.invalidate_header();
+        self.border_state = None;
+        let mut buttons = self.buttons.borrow_mut();
+        buttons.set_layout(config.button_layout);
+        buttons.set_sizes(Sizes {
+            header: config.header_height,
+            border: config.border_size,
+        });
+        drop(buttons);
+        self.corner_radius = config.corner_radius;
+        let title_font = config.title_font.or_else(|| {
+            let shell = config.shell.as_ref()?;
+            shell.settings.titlebar_font.value.clone()
+        });
+        if title_font != self.title_font {
+            self.title_font = title_font;
+            self.title_text = TitleText::new(
+                self.colors.for_state(self.active).font_color,
+                self.title_font.as_deref(),
+            );
+            if let (Some(title_text), Some(title)) = (self.title_text.as_mut(), &self.title) {
+                title_text.update_title(title.as_str());
+            }
+        }
+        let mut inner = self.inner.borrow_mut();
+        inner.axis_maximize = config.axis_maximize;
+        inner.titlebar_axis = config.titlebar_axis;
+        inner.button_scroll = config.button_scroll;
+        inner.redraw_metrics = config.redraw_metrics;
+        inner.redraw_error = config.redraw_error;
+        let missing_global = inner
+            .redraw_error
+            .clone()
+            .filter(|_| self.subcompositor_missing);
+        inner.decoration_events = config.decoration_events;
+        inner.header_mode = config.header_mode;
+        inner.drag_regions = config.drag_regions;
+        inner.reserved_area = config.reserved_area;
+        inner.dirty = true;
+        inner.parts.set_viewporter(config.viewporter);
+        inner.parts.set_scale_override(
+            config
+                .scale_factor
+                .map(|scale| scale.ceil().max(1.0) as u32),
+        );
+        drop(inner);
+        self.set_server_decoration_manager(config.kde_server_decoration);
+        self.anti_alias = config.anti_alias;
+        self.shadows = config.shadows;
+        if config.suspended && !self.suspended {
+            debug!("Decorations suspended");
+            self.animations.clear();
+        }
+        self.suspended = config.suspended;
+        self.animate_visibility = config.animate_visibility
+            && config
+                .shell
+                .as_ref()
+                .map_or_else(config::animations_enabled, |shell| {
+                    shell.settings.animations.value
+                });
+        if let Some(shell) = config.shell {
+            if !Rc::ptr_eq(&self.pool, &shell.pool) {
+                self.pool
+                    .borrow_mut()
+                    .forget(Rc::as_ptr(&self.inner) as usize);
+                self.pool = shell.pool;
+            }
+        }
+        self.pixel_format = match config.shm_formats {
+            Some(formats) => PixelFormat::negotiate(config.pixel_format, &formats),
+            None => config.pixel_format,
+        };
+        if let Some(callback) = missing_global {
+            callback(&DecorationError::MissingGlobal("wl_subcompositor"));
+        }
+
+        // `Window::set_frame_config` doesn't refresh the frame, the new look is shown with the
+        // next commit of the window.
+        if !self.hidden {
+            self.redraw();
+        }
     }
 
     fn set_title(&mut self, title: String) {
+        // Clients commonly set the same title on every frame.
+        if self.title.as_ref() == Some(&title) {
+            return;
+        }
+
         if let Some(title_text) = self.title_text.as_mut() {
             title_text.update_title(&title);
         }
 
         self.title = Some(title);
+        self.invalidate_header();
+        self.inner.borrow_mut().dirty = true;
     }
 }
 
 impl AdwaitaFrame {
-    fn redraw_inner(&mut self) -> SkiaResult {
-        let inner = self.inner.borrow_mut();
+    /// Title last set on the window, drawn in the header if a title font is available.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Whether the window is active, as of the last `set_states`.
+    pub fn window_state(&self) -> WindowState {
+        self.active
+    }
+
+    pub fn is_maximized(&self) -> bool {
+        self.inner.borrow().maximized
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.inner.borrow().fullscreened
+    }
+
+    /// Edges the window is tiled on.
+    pub fn tiled(&self) -> Edges {
+        self.inner.borrow().tiled
+    }
+
+    pub fn is_resizable(&self) -> bool {
+        self.inner.borrow().resizable
+    }
+
+    /// Whether the decorations are hidden with `set_hidden`, e.g. while the compositor draws
+    /// server-side decorations, or for good without a `wl_subcompositor`.
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /// Size of the window content last passed to `resize`, in logical pixels and clamped by
+    /// [`sanitize_size`].
+    pub fn size(&self) -> (u32, u32) {
+        self.inner.borrow().size
+    }
+
+    /// Decorations belonging to the window geometry, the header while it is shown.
+    ///
+    /// The xdg window geometry is the content grown by these insets, like `add_borders` does.
+    pub fn insets(&self) -> DecorationInsets {
+        if self.hidden || self.inner.borrow().undecorated() {
+            DecorationInsets::default()
+        } else {
+            DecorationInsets {
+                top: self.header_height(),
+                ..DecorationInsets::default()
+            }
+        }
+    }
+
+    /// Decorations outside of the window geometry, the resize margins which also hold the
+    /// shadow.
+    ///
+    /// Hidden borders, e.g. of maximized windows, have no margin, but the header surface still
+    /// reaches a border's width above the window geometry.
+    pub fn margins(&self) -> DecorationInsets {
+        let inner = self.inner.borrow();
+        if self.hidden || inner.undecorated() {
+            return DecorationInsets::default();
+        }
+
+        let border = self.buttons.borrow().sizes().border;
+        let hidden = inner.hidden_borders();
+        let margin = |hidden: bool| if hidden { 0 } else { border };
+        DecorationInsets {
+            top: border,
+            left: margin(hidden.left),
+            right: margin(hidden.right),
+            bottom: margin(hidden.bottom),
+        }
+    }
+
+    /// The decoration `surface` belongs to, e.g. to convert the coordinates of pointer or
+    /// drag-and-drop events with [`AdwaitaFrame::part_to_content`].
+    pub fn decoration_part(&self, surface: &wl_surface::WlSurface) -> Option<DecorationPart> {
+        self.inner
+            .borrow()
+            .parts
+            .find_decoration_part(surface)
+            .part()
+    }
+
+    /// Reports a `wl_data_device` enter on `surface` as [`DecorationEvent::DndEnter`] if it is
+    /// a decoration of this frame, and returns whether it was.
+    ///
+    /// The frame doesn't bind a data device of its own, the embedder forwards the enters of its
+    /// device, and the leaves and drops with [`AdwaitaFrame::dnd_leave`].
+    pub fn dnd_enter(&mut self, surface: &wl_surface::WlSurface) -> bool {
+        // An enter implies leaving the previous surface.
+        self.dnd_leave();
+        let Some(part) = self.decoration_part(surface) else {
+            return false;
+        };
+        self.dnd_entered = true;
+        let decoration_events = self.inner.borrow().decoration_events.clone();
+        if let Some(callback) = decoration_events {
+            callback(&DecorationEvent::DndEnter(part));
+        }
+        true
+    }
 
-        // Don't draw borders if the frame explicitly hidden or fullscreened.
-        if self.hidden || inner.fullscreened {
-            inner.parts.hide_decorations();
-            return Some(());
+    /// Reports the end of a drag entered with [`AdwaitaFrame::dnd_enter`] as
+    /// [`DecorationEvent::DndLeave`], whether it left the decorations or was dropped on them.
+    ///
+    /// Does nothing unless the drag is over the decorations of this frame.
+    pub fn dnd_leave(&mut self) {
+        if !std::mem::take(&mut self.dnd_entered) {
+            return;
+        }
+        let decoration_events = self.inner.borrow().decoration_events.clone();
+        if let Some(callback) = decoration_events {
+            callback(&DecorationEvent::DndLeave);
+        }
+    }
+
+    /// Position of the `part` surface relative to the window content, in logical pixels.
+    pub fn part_origin(&self, part: DecorationPart) -> (i32, i32) {
+        let inner = self.inner.borrow();
+        let sizes = inner.header_mode.geometry(self.buttons.borrow().sizes());
+        part_origin(part, sizes, inner.size)
+    }
+
+    /// Converts a position on the `part` surface to the window content surface.
+    pub fn part_to_content(&self, part: DecorationPart, x: f64, y: f64) -> (f64, f64) {
+        let (origin_x, origin_y) = self.part_origin(part);
+        (x + f64::from(origin_x), y + f64::from(origin_y))
+    }
+
+    /// Converts a position on the window content surface to the `part` surface.
+    pub fn content_to_part(&self, part: DecorationPart, x: f64, y: f64) -> (f64, f64) {
+        let (origin_x, origin_y) = self.part_origin(part);
+        (x - f64::from(origin_x), y - f64::from(origin_y))
+    }
+
+    /// Converts a position on the window content surface to the window geometry, which starts
+    /// at the top left corner of the header, e.g. for `xdg_positioner` anchor rectangles.
+    pub fn content_to_geometry(&self, x: f64, y: f64) -> (f64, f64) {
+        (x, y + f64::from(self.insets().top))
+    }
+
+    /// Converts a position in the window geometry to the window content surface.
+    pub fn geometry_to_content(&self, x: f64, y: f64) -> (f64, f64) {
+        (x, y - f64::from(self.insets().top))
+    }
+
+    /// Whether the next redraw would change the decorations, e.g. after a state, size or hover
+    /// change, or while an animation is running.
+    ///
+    /// Embedders batching their rendering can skip [`Frame::redraw`] otherwise. Always `false`
+    /// while suspended, since nothing is drawn then.
+    pub fn needs_redraw(&self) -> bool {
+        !self.suspended && (self.inner.borrow().dirty || self.animations.is_running())
+    }
+
+    /// Cost and damage of the last redraw, e.g. for embedders coalescing damage in their own
+    /// frame pacing.
+    pub fn last_redraw(&self) -> &RedrawMetrics {
+        &self.redraw_metrics
+    }
+
+    /// Follows the decoration mode KWin picks for the window.
+    fn set_server_decoration_manager(
+        &mut self,
+        manager: Option<Attached<OrgKdeKwinServerDecorationManager>>,
+    ) {
+        match (manager, self.server_decoration.is_some()) {
+            (Some(manager), false) => {
+                let decoration = manager.create(&self.base_surface);
+                let inner = Rc::downgrade(&self.inner);
+                decoration.quick_assign(move |_, event, ddata| {
+                    let org_kde_kwin_server_decoration::Event::Mode { mode } = event else {
+                        return;
+                    };
+                    let Some(inner) = inner.upgrade() else {
+                        return;
+                    };
+
+                    let mut guard = inner.borrow_mut();
+                    let server_decorated = mode == ServerDecorationMode::Server;
+                    if guard.server_decorated != server_decorated {
+                        debug!("KWin decoration mode changed to {:?}", mode);
+                        guard.server_decorated = server_decorated;
+                        guard.dirty = true;
+                        guard.request(FrameRequest::Refresh, 0);
+                        drop(guard);
+                        dispatch(&inner, ddata);
+                    }
+                });
+                self.server_decoration = Some(decoration.detach());
+            }
+            (None, true) => {
+                if let Some(decoration) = self.server_decoration.take() {
+                    decoration.release();
+                }
+                self.inner.borrow_mut().server_decorated = false;
+            }
+            _ => {}
+        }
+    }
+
+    /// Height the header adds above the window content.
+    fn header_height(&self) -> u32 {
+        let sizes = self.buttons.borrow().sizes();
+        self.inner.borrow().header_mode.geometry(sizes).header
+    }
+
+    /// Fades the decorations in, if enabled.
+    fn start_appear(&mut self) {
+        if self.animate_visibility && !self.suspended {
+            self.animations.start(
+                AnimationKind::Appear,
+                Duration::from_millis(150),
+                Instant::now(),
+            );
+        }
+    }
+
+    /// Forces a full redraw of the header on the next `redraw`.
+    fn invalidate_header(&mut self) {
+        if let Some(cache) = self.header_cache.as_mut() {
+            cache.state = None;
+        }
+    }
+
+    fn redraw_inner(&mut self) -> Result<(), DecorationError> {
+        let now = Instant::now();
+        self.animations.tick(now);
+
+        let mut inner = self.inner.borrow_mut();
+
+        // Don't draw borders if the frame explicitly hidden, fullscreened or decorated by KWin.
+        if self.hidden || inner.undecorated() {
+            inner.parts.hide_decorations(now);
+            // Unmapped, the header doesn't get frame callbacks to tick animations anymore.
+            self.animations.clear();
+            drop(inner);
+            self.invalidate_header();
+            self.border_state = None;
+            return Ok(());
+        }
+
+        let hidden = inner.hidden_borders();
+        if hidden.left && hidden.right && hidden.bottom {
+            inner.parts.hide_borders(now);
+        } else {
+            inner.parts.show_borders(
+                &self.base_surface,
+                &self.compositor,
+                &self.subcompositor,
+                self.inner.clone(),
+            );
         }
 
         // `parts` can't be empty here, since the initial state for `self.hidden` is true, and
@@ -379,242 +1434,492 @@ impl AdwaitaFrame {
         let parts = &inner.parts;
 
         let (width, height) = inner.size;
+        let sizes = self.buttons.borrow().sizes();
+        let header_mode = inner.header_mode;
+        let geometry = header_mode.geometry(sizes);
 
         if let Some(decoration) = parts.decoration() {
             // Use header scale for all the thing.
             let header_scale = decoration.header.scale();
-            self.buttons.borrow_mut().update_scale(header_scale);
+            let mut buttons = self.buttons.borrow_mut();
+            if buttons.scale() != header_scale {
+                debug!(
+                    "Decoration scale changed from {} to {}",
+                    buttons.scale(),
+                    header_scale
+                );
+                buttons.update_scale(header_scale);
+            }
+            drop(buttons);
 
-            let left_scale = decoration.left.scale();
-            let right_scale = decoration.right.scale();
-            let bottom_scale = decoration.bottom.scale();
+            // Buffer transforms need `wl_surface` version 2.
+            let transform = |part: &parts::Part| {
+                if self.surface_version >= 2 {
+                    part.transform()
+                } else {
+                    wl_output::Transform::Normal
+                }
+            };
+            let header_transform = transform(&decoration.header);
 
             let (header_width, header_height) = self.buttons.borrow().scaled_size();
-            let header_height = header_height + BORDER_SIZE * header_scale;
+            let header_height =
+                header_height.saturating_add(sizes.border.saturating_mul(header_scale));
+
+            // Saturates for scales too large to draw with, those buffers fail on their own.
+            let area = |width: u32, height: u32, scale: u32| {
+                (width as usize)
+                    .saturating_mul(height as usize)
+                    .saturating_mul((scale as usize).saturating_pow(2))
+            };
+            let border_area = decoration.borders.as_ref().map_or(0, |borders| {
+                area(
+                    width + 2 * sizes.border,
+                    sizes.border,
+                    borders.bottom.scale(),
+                )
+                .saturating_add(area(sizes.border, height, borders.left.scale()))
+                .saturating_add(area(sizes.border, height, borders.right.scale()))
+            });
+            self.pool.borrow_mut().trim(
+                Rc::as_ptr(&self.inner) as usize,
+                area(header_width, header_height, 1)
+                    .saturating_add(border_area)
+                    .saturating_mul(4),
+            );
 
             {
                 // Create the buffers and draw
 
-                let colors = if self.active == WindowState::Active {
-                    &self.colors.active
-                } else {
-                    &self.colors.inactive
+                let fade = self.animations.progress(AnimationKind::Crossfade, now);
+                let colors = match fade {
+                    Some(t) => {
+                        let from = match self.active {
+                            WindowState::Active => WindowState::Inactive,
+                            WindowState::Inactive => WindowState::Active,
+                        };
+                        let from = self.colors.for_state(from);
+                        from.mix(self.colors.for_state(self.active), t)
+                    }
+                    None => self.colors.for_state(self.active).clone(),
                 };
+                let colors = &colors;
+                // Every step of the fade is a new state.
+                let fade = fade.map(|t| (t * 1000.0) as u16);
+
+                let appear = self.animations.progress(AnimationKind::Appear, now);
+                let opacity = appear.unwrap_or(1.0);
+                let appear = appear.map(|t| (t * 1000.0) as u16);
 
                 if let Some(title_text) = self.title_text.as_mut() {
                     title_text.update_color(colors.font_color);
                 }
 
                 let border_paint = colors.border_paint();
+                let shadow = self.shadows
+                    && self.active == WindowState::Active
+                    && !inner.maximized
+                    && !inner.tiled.any();
+                let shadow_at = |part: DecorationPart, scale: u32| {
+                    let origin = part_origin(part, geometry, (width, height));
+                    shadow.then_some(Shadow {
+                        origin: (origin.0 as f32, origin.1 as f32),
+                        window: (width, height),
+                        scale,
+                        corner_radius: self.corner_radius,
+                        sizes: geometry,
+                    })
+                };
 
                 // -> head-subsurface
-                if let Ok((canvas, buffer)) = self.pool.buffer(
-                    header_width as i32,
-                    header_height as i32,
-                    4 * header_width as i32,
-                    wl_shm::Format::Argb8888,
-                ) {
-                    let mut pixmap = PixmapMut::from_bytes(canvas, header_width, header_height)?;
-                    pixmap.fill(Color::TRANSPARENT);
-
-                    if let Some(title_text) = self.title_text.as_mut() {
-                        title_text.update_scale(header_scale);
+                let mut hovered = [false; 3];
+                for pointer in self.pointers.iter().filter(|p| p.as_ref().is_alive()) {
+                    let data = pointer
+                        .as_ref()
+                        .user_data()
+                        .get::<RefCell<PointerUserData>>();
+                    if let Some(Location::Button(kind)) = data.map(|d| d.borrow().state.location) {
+                        hovered[kind as usize] = true;
                     }
+                }
+                let header_state = HeaderState {
+                    width: header_width,
+                    height: header_height,
+                    scale: header_scale,
+                    active: self.active,
+                    resizable: inner.resizable,
+                    maximized: inner.maximized,
+                    tiled: inner.tiled,
+                    shadow,
+                    fade,
+                    appear,
+                };
 
-                    draw_headerbar(
-                        &mut pixmap,
-                        self.title_text.as_ref().map(|t| t.pixmap()).unwrap_or(None),
-                        header_scale as f32,
-                        inner.resizable,
-                        inner.maximized,
-                        inner.tiled,
-                        self.active,
-                        &self.colors,
-                        &self.buttons.borrow(),
-                        &self
-                            .pointers
-                            .iter()
-                            .flat_map(|p| {
-                                if p.as_ref().is_alive() {
-                                    let data: &RefCell<PointerUserData> =
-                                        p.as_ref().user_data().get()?;
-                                    Some(data.borrow().location)
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect::<Vec<Location>>(),
-                    );
+                // Colors in between palettes are not worth caching.
+                let icons = if fade.is_some() {
+                    ButtonIcons::default()
+                } else {
+                    self.button_icons.clone()
+                };
 
-                    decoration.header.subsurface.set_position(
-                        -(BORDER_SIZE as i32),
-                        -(HEADER_SIZE as i32 + BORDER_SIZE as i32),
-                    );
-                    decoration.header.surface.attach(Some(&buffer), 0, 0);
-                    if self.surface_version >= 4 {
-                        decoration.header.surface.damage_buffer(
-                            0,
-                            0,
-                            header_width as i32,
-                            header_height as i32,
+                let mut header_error = None;
+                let damage = match self.header_cache.as_mut() {
+                    Some(cache) if cache.state == Some(header_state) => {
+                        // Only hover changes are left, redraw the affected buttons.
+                        let buttons = self.buttons.borrow();
+                        let bg = button_clear_paint(colors, header_mode);
+
+                        let mut damage: Option<Rect> = None;
+                        for (id, kind) in ButtonKind::ALL.into_iter().enumerate() {
+                            if cache.hovered[id] == hovered[id] {
+                                continue;
+                            }
+
+                            let rect = buttons.get(kind).rect().ok_or(DecorationError::Render)?;
+                            let rect = Rect::from_ltrb(
+                                rect.left() - 1.0,
+                                rect.top() - 1.0,
+                                rect.right() + 1.0,
+                                rect.bottom() + 1.0,
+                            )
+                            .ok_or(DecorationError::Render)?;
+                            cache
+                                .pixmap
+                                .fill_rect(rect, &bg, Transform::identity(), None);
+                            draw_button(
+                                &mut cache.pixmap.as_mut(),
+                                &icons,
+                                kind,
+                                header_scale,
+                                colors,
+                                self.active == WindowState::Active,
+                                &buttons,
+                                hovered,
+                                inner.resizable,
+                                inner.maximized,
+                                self.anti_alias,
+                            );
+
+                            damage = Some(match damage {
+                                Some(damage) => Rect::from_ltrb(
+                                    damage.left().min(rect.left()),
+                                    damage.top().min(rect.top()),
+                                    damage.right().max(rect.right()),
+                                    damage.bottom().max(rect.bottom()),
+                                )
+                                .ok_or(DecorationError::Render)?,
+                                None => rect,
+                            });
+                        }
+                        cache.hovered = hovered;
+                        damage
+                    }
+                    _ => 'header: {
+                        // Reuse the previous pixmap unless the header got resized.
+                        let mut pixmap = match self.header_cache.take() {
+                            Some(HeaderCache { mut pixmap, .. })
+                                if pixmap.width() == header_width
+                                    && pixmap.height() == header_height =>
+                            {
+                                pixmap.fill(Color::TRANSPARENT);
+                                pixmap
+                            }
+                            _ => {
+                                // Checked before allocating, a failed allocation would abort
+                                // rather than return `None`. A larger header couldn't be
+                                // committed anyway.
+                                if let Err(err) = buffer_stride(header_width, header_height) {
+                                    header_error = Some(err);
+                                    break 'header None;
+                                }
+                                match Pixmap::new(header_width, header_height) {
+                                    Some(pixmap) => pixmap,
+                                    // Only an empty size is left to fail.
+                                    None => {
+                                        header_error = Some(DecorationError::Render);
+                                        break 'header None;
+                                    }
+                                }
+                            }
+                        };
+
+                        if let Some(title_text) = self.title_text.as_mut() {
+                            title_text.update_scale(header_scale);
+                        }
+
+                        if let Some(shadow) = shadow_at(DecorationPart::Header, header_scale) {
+                            draw_header_shadow(&mut pixmap.as_mut(), &shadow, header_mode);
+                        }
+
+                        let drawn = draw_headerbar(
+                            &mut pixmap.as_mut(),
+                            self.title_text.as_ref().map(|t| t.pixmap()).unwrap_or(None),
+                            header_scale as f32,
+                            inner.resizable,
+                            inner.maximized,
+                            inner.tiled,
+                            self.corner_radius,
+                            self.active == WindowState::Active,
+                            header_mode,
+                            colors,
+                            &self.buttons.borrow(),
+                            &icons,
+                            hovered,
+                            self.anti_alias,
+                            &mut self.header_scratch,
+                        );
+                        if drawn.is_none() {
+                            header_error = Some(DecorationError::Render);
+                            break 'header None;
+                        }
+                        if let Some(area) = inner.reserved_area {
+                            clear_reserved_area(
+                                &mut pixmap.as_mut(),
+                                area,
+                                width,
+                                sizes,
+                                header_scale,
+                            );
+                        }
+
+                        set_header_input_region(
+                            &decoration.header.surface,
+                            &self.compositor,
+                            header_mode,
+                            &self.buttons.borrow(),
                         );
+
+                        self.header_cache = Some(HeaderCache {
+                            state: Some(header_state),
+                            hovered,
+                            pixmap,
+                        });
+                        Rect::from_xywh(0.0, 0.0, header_width as f32, header_height as f32)
+                    }
+                };
+
+                // The frame callback of the next header commit requests the next redraw.
+                let implem = self.inner.clone();
+                let animating =
+                    self.animations
+                        .schedule(&decoration.header.surface, move |ddata| {
+                            let mut inner = implem.borrow_mut();
+                            inner.dirty = true;
+                            inner.request(FrameRequest::Refresh, 0);
+                            drop(inner);
+                            dispatch(&implem, ddata);
+                        });
+
+                let mut composer = Composer {
+                    pool: &self.pool,
+                    pixel_format: self.pixel_format,
+                    surface_version: self.surface_version,
+                    opacity,
+                    metrics: &mut self.redraw_metrics,
+                };
+
+                // Without damage nothing changed since the last commit, keep the attached buffer.
+                if let (Some(damage), Some(cache)) = (damage, self.header_cache.as_ref()) {
+                    let (buffer_width, buffer_height) =
+                        transform::buffer_size(header_transform, header_width, header_height);
+                    let damage = if header_transform == wl_output::Transform::Normal {
+                        damage.round_out().ok_or(DecorationError::Render)?
                     } else {
-                        // surface is old and does not support damage_buffer, so we damage
-                        // in surface coordinates and hope it is not rescaled
+                        IntRect::from_xywh(0, 0, buffer_width, buffer_height)
+                            .ok_or(DecorationError::Render)?
+                    };
+                    let surface_size = (width + 2 * sizes.border, sizes.header + sizes.border);
+                    let surface_damage = transform::surface_damage(
+                        damage,
+                        header_scale,
+                        header_transform,
+                        surface_size,
+                    )
+                    .ok_or(DecorationError::Render)?;
+                    let placement = Placement {
+                        kind: DecorationPart::Header,
+                        size: (header_width, header_height),
+                        transform: header_transform,
+                        position: part_origin(DecorationPart::Header, geometry, (width, height)),
+                        surface_size,
+                        damage: Some((damage, surface_damage)),
+                    };
+                    if self.header_fallback {
                         decoration
                             .header
                             .surface
-                            .damage(0, 0, width as i32, HEADER_SIZE as i32);
+                            .set_buffer_scale(header_scale as i32);
+                        self.header_fallback = false;
                     }
+                    let result = composer.commit(&decoration.header, placement, |canvas| {
+                        transform::copy_transformed(
+                            cache.pixmap.data(),
+                            header_width,
+                            header_height,
+                            header_transform,
+                            canvas,
+                        );
+                        Ok(())
+                    });
+                    if let Err(err) = result {
+                        header_error = Some(err);
+                    }
+                } else if animating {
                     decoration.header.surface.commit();
                 }
 
-                if inner.maximized {
-                    // Don't draw the borders.
-                    decoration.hide_borders();
-                    return Some(());
-                }
-
-                let w = ((width + 2 * BORDER_SIZE) * bottom_scale) as i32;
-                let h = (BORDER_SIZE * bottom_scale) as i32;
-                // -> bottom-subsurface
-                if let Ok((canvas, buffer)) = self.pool.buffer(
-                    w,
-                    h,
-                    (4 * bottom_scale * (width + 2 * BORDER_SIZE)) as i32,
-                    wl_shm::Format::Argb8888,
-                ) {
-                    let mut pixmap = PixmapMut::from_bytes(canvas, w as u32, h as u32)?;
-                    pixmap.fill(Color::TRANSPARENT);
-
-                    let size = 1.0;
-                    let x = BORDER_SIZE as f32 * bottom_scale as f32 - 1.0;
-                    pixmap.fill_rect(
-                        Rect::from_xywh(
-                            x,
+                // Rather than leave the window without its buttons, fall back to a plain header
+                // at buffer scale 1: no title, shadow, rounded corners or anti-aliasing. Its
+                // buffer is scale² smaller, so it may still fit where the full one didn't. The
+                // first error still fails the redraw, so the next one tries the full header
+                // again.
+                let plain_header = header_error
+                    .as_ref()
+                    .is_some_and(DecorationError::is_recoverable);
+                if plain_header {
+                    warn!("Falling back to a plain header");
+                    let surface_size = (width + 2 * sizes.border, sizes.header + sizes.border);
+                    let mut buttons = self.buttons.borrow().clone();
+                    buttons.update_scale(1);
+                    decoration.header.surface.set_buffer_scale(1);
+                    self.header_fallback = true;
+                    let placement = Placement {
+                        kind: DecorationPart::Header,
+                        size: surface_size,
+                        transform: wl_output::Transform::Normal,
+                        position: part_origin(DecorationPart::Header, geometry, (width, height)),
+                        surface_size,
+                        damage: None,
+                    };
+                    let header_scratch = &mut self.header_scratch;
+                    let result = composer.commit(&decoration.header, placement, |canvas| {
+                        let (w, h) = surface_size;
+                        let mut pixmap =
+                            PixmapMut::from_bytes(canvas, w, h).ok_or(DecorationError::Render)?;
+                        pixmap.fill(Color::TRANSPARENT);
+                        draw_headerbar(
+                            &mut pixmap,
+                            None,
+                            1.0,
+                            inner.resizable,
+                            inner.maximized,
+                            inner.tiled,
                             0.0,
-                            w as f32 - BORDER_SIZE as f32 * 2.0 * bottom_scale as f32 + 2.0,
-                            size,
-                        )?,
-                        &border_paint,
-                        Transform::identity(),
-                        None,
-                    );
-
-                    decoration
-                        .bottom
-                        .subsurface
-                        .set_position(-(BORDER_SIZE as i32), height as i32);
-                    decoration.bottom.surface.attach(Some(&buffer), 0, 0);
-                    if self.surface_version >= 4 {
-                        decoration.bottom.surface.damage_buffer(
-                            0,
-                            0,
-                            ((width + 2 * BORDER_SIZE) * bottom_scale) as i32,
-                            (BORDER_SIZE * bottom_scale) as i32,
-                        );
-                    } else {
-                        // surface is old and does not support damage_buffer, so we damage
-                        // in surface coordinates and hope it is not rescaled
-                        decoration.bottom.surface.damage(
-                            0,
-                            0,
-                            (width + 2 * BORDER_SIZE) as i32,
-                            BORDER_SIZE as i32,
-                        );
+                            self.active == WindowState::Active,
+                            header_mode,
+                            colors,
+                            &buttons,
+                            &icons,
+                            hovered,
+                            false,
+                            header_scratch,
+                        )
+                        .ok_or(DecorationError::Render)?;
+                        if let Some(area) = inner.reserved_area {
+                            clear_reserved_area(&mut pixmap, area, width, sizes, 1);
+                        }
+                        Ok(())
+                    });
+                    if let Err(err) = result {
+                        warn!("Failed to draw the plain header: {}", err);
                     }
-                    decoration.bottom.surface.commit();
                 }
 
-                let w = (BORDER_SIZE * left_scale) as i32;
-                let h = (height * left_scale) as i32;
-                // -> left-subsurface
-                if let Ok((canvas, buffer)) = self.pool.buffer(
-                    w,
-                    h,
-                    4 * (BORDER_SIZE * left_scale) as i32,
-                    wl_shm::Format::Argb8888,
-                ) {
-                    let mut bg = Paint::default();
-                    bg.set_color_rgba8(255, 0, 0, 255);
-
-                    let mut pixmap = PixmapMut::from_bytes(canvas, w as u32, h as u32)?;
-                    pixmap.fill(Color::TRANSPARENT);
-
-                    let size = 1.0;
-                    pixmap.fill_rect(
-                        Rect::from_xywh(w as f32 - size, 0.0, w as f32, h as f32)?,
-                        &border_paint,
-                        Transform::identity(),
-                        None,
-                    );
+                let Some(borders) = decoration.borders.as_ref() else {
+                    // Maximized, all borders are hidden.
+                    self.border_state = None;
+                    return header_error.map_or(Ok(()), Err);
+                };
 
-                    decoration
-                        .left
-                        .subsurface
-                        .set_position(-(BORDER_SIZE as i32), 0);
-                    decoration.left.surface.attach(Some(&buffer), 0, 0);
-                    if self.surface_version >= 4 {
-                        decoration.left.surface.damage_buffer(0, 0, w, h);
-                    } else {
-                        // surface is old and does not support damage_buffer, so we damage
-                        // in surface coordinates and hope it is not rescaled
-                        decoration.left.surface.damage(
-                            0,
-                            0,
-                            BORDER_SIZE as i32,
-                            (height + HEADER_SIZE) as i32,
-                        );
-                    }
-                    decoration.left.surface.commit();
+                let border_parts = [
+                    (DecorationPart::Bottom, &borders.bottom, hidden.bottom),
+                    (DecorationPart::Left, &borders.left, hidden.left),
+                    (DecorationPart::Right, &borders.right, hidden.right),
+                ];
+                let scales = border_parts.map(|(_, part, _)| part.scale());
+                let transforms = border_parts.map(|(_, part, _)| transform(part));
+
+                let border_state = BorderState {
+                    size: inner.size,
+                    scales,
+                    transforms,
+                    hidden,
+                    active: self.active,
+                    shadow,
+                    fade,
+                    appear,
+                };
+                // The attached border buffers are still up to date.
+                if self.border_state == Some(border_state) {
+                    return header_error.map_or(Ok(()), Err);
                 }
 
-                let w = (BORDER_SIZE * right_scale) as i32;
-                let h = (height * right_scale) as i32;
-                // -> right-subsurface
-                if let Ok((canvas, buffer)) = self.pool.buffer(
-                    w,
-                    h,
-                    4 * (BORDER_SIZE * right_scale) as i32,
-                    wl_shm::Format::Argb8888,
-                ) {
-                    let mut bg = Paint::default();
-                    bg.set_color_rgba8(255, 0, 0, 255);
-
-                    let mut pixmap = PixmapMut::from_bytes(canvas, w as u32, h as u32)?;
-                    pixmap.fill(Color::TRANSPARENT);
-
-                    let size = 1.0;
-                    pixmap.fill_rect(
-                        Rect::from_xywh(0.0, 0.0, size, h as f32)?,
-                        &border_paint,
-                        Transform::identity(),
-                        None,
-                    );
-
-                    decoration.right.subsurface.set_position(width as i32, 0);
-                    decoration.right.surface.attach(Some(&buffer), 0, 0);
-                    if self.surface_version >= 4 {
-                        decoration.right.surface.damage_buffer(0, 0, w, h);
-                    } else {
-                        // surface is old and does not support damage_buffer, so we damage
-                        // in surface coordinates and hope it is not rescaled
-                        decoration
-                            .right
-                            .surface
-                            .damage(0, 0, BORDER_SIZE as i32, height as i32);
+                for (id, (kind, part, hidden)) in border_parts.into_iter().enumerate() {
+                    // Side borders of a window without height are empty, and viewports can't
+                    // be.
+                    if hidden || (kind != DecorationPart::Bottom && height == 0) {
+                        clear_part(part);
+                        continue;
                     }
-                    decoration.right.surface.commit();
+
+                    let (scale, transform) = (scales[id], transforms[id]);
+                    let surface_size = match kind {
+                        DecorationPart::Bottom => (width + 2 * sizes.border, sizes.border),
+                        _ => (sizes.border, height),
+                    };
+                    let size = match kind {
+                        // With a viewport a single row is stretched to the full height.
+                        _ if part.viewport.is_some() => (sizes.border * scale, scale),
+                        _ => (surface_size.0 * scale, surface_size.1 * scale),
+                    };
+                    let placement = Placement {
+                        kind,
+                        size,
+                        transform,
+                        position: part_origin(kind, geometry, (width, height)),
+                        surface_size,
+                        damage: None,
+                    };
+                    let shadow = shadow_at(kind, scale);
+                    composer.commit(part, placement, |canvas| {
+                        let (w, h) = size;
+                        draw_result(transform::draw_transformed(
+                            canvas,
+                            w,
+                            h,
+                            transform,
+                            |canvas| match kind {
+                                DecorationPart::Bottom => {
+                                    draw_bottom_border(canvas, w, h, &border_paint, shadow)
+                                }
+                                DecorationPart::Left => {
+                                    draw_left_border(canvas, w, h, &border_paint, shadow)
+                                }
+                                _ => draw_right_border(canvas, w, h, &border_paint, shadow),
+                            },
+                        ))
+                    })?;
+                }
+
+                self.border_state = Some(border_state);
+                if let Some(err) = header_error {
+                    return Err(err);
                 }
             }
         }
 
-        Some(())
+        Ok(())
     }
 }
 
 impl Drop for AdwaitaFrame {
     fn drop(&mut self) {
+        self.pool
+            .borrow_mut()
+            .forget(Rc::as_ptr(&self.inner) as usize);
+        if let Some(decoration) = self.server_decoration.take() {
+            decoration.release();
+        }
         for ptr in self.pointers.drain(..) {
             if ptr.as_ref().version() >= 3 {
                 ptr.release();
@@ -622,206 +1927,3 @@ impl Drop for AdwaitaFrame {
         }
     }
 }
-
-fn draw_headerbar(
-    pixmap: &mut PixmapMut,
-    text_pixmap: Option<&Pixmap>,
-    scale: f32,
-    maximizable: bool,
-    is_maximized: bool,
-    tiled: bool,
-    state: WindowState,
-    colors: &ColorTheme,
-    buttons: &Buttons,
-    mouses: &[Location],
-) {
-    let border_size = BORDER_SIZE as f32 * scale;
-
-    let margin_h = border_size;
-    let margin_v = border_size;
-
-    let colors = colors.for_state(state);
-
-    draw_headerbar_bg(
-        pixmap,
-        scale,
-        margin_h,
-        margin_v,
-        colors,
-        is_maximized,
-        tiled,
-    );
-
-    if let Some(text_pixmap) = text_pixmap {
-        let canvas_w = pixmap.width() as f32;
-        let canvas_h = pixmap.height() as f32;
-
-        let header_w = canvas_w - margin_h * 2.0;
-        let header_h = canvas_h - margin_v;
-
-        let text_w = text_pixmap.width() as f32;
-        let text_h = text_pixmap.height() as f32;
-
-        let x = header_w / 2.0 - text_w / 2.0;
-        let y = header_h / 2.0 - text_h / 2.0;
-
-        let x = margin_h + x;
-        let y = margin_v + y;
-
-        let (x, y) = if x + text_w < buttons.minimize.x() - 10.0 {
-            (x, y)
-        } else {
-            let y = header_h / 2.0 - text_h / 2.0;
-
-            let x = buttons.minimize.x() - text_w - 10.0;
-            let y = margin_v + y;
-            (x, y)
-        };
-
-        let x = x.max(margin_h + 5.0);
-
-        if let Some(clip) = Rect::from_xywh(0.0, 0.0, buttons.minimize.x() - 10.0, canvas_h) {
-            let mut mask = ClipMask::new();
-            mask.set_path(
-                canvas_w as u32,
-                canvas_h as u32,
-                &PathBuilder::from_rect(clip),
-                FillRule::Winding,
-                false,
-            );
-            pixmap.draw_pixmap(
-                x as i32,
-                y as i32,
-                text_pixmap.as_ref(),
-                &PixmapPaint::default(),
-                Transform::identity(),
-                Some(&mask),
-            );
-        }
-    }
-
-    if buttons.close.x() > margin_h {
-        buttons.close.draw_close(scale, colors, mouses, pixmap);
-    }
-
-    if buttons.maximize.x() > margin_h {
-        buttons
-            .maximize
-            .draw_maximize(scale, colors, mouses, maximizable, is_maximized, pixmap);
-    }
-
-    if buttons.minimize.x() > margin_h {
-        buttons
-            .minimize
-            .draw_minimize(scale, colors, mouses, pixmap);
-    }
-}
-
-fn draw_headerbar_bg(
-    pixmap: &mut PixmapMut,
-    scale: f32,
-    margin_h: f32,
-    margin_v: f32,
-    colors: &ColorMap,
-    is_maximized: bool,
-    tiled: bool,
-) -> SkiaResult {
-    let w = pixmap.width() as f32;
-    let h = pixmap.height() as f32;
-
-    let radius = if is_maximized || tiled {
-        0.0
-    } else {
-        10.0 * scale
-    };
-
-    let margin_h = margin_h - 1.0;
-    let w = w - margin_h * 2.0;
-
-    let bg = rounded_headerbar_shape(margin_h, margin_v, w, h, radius)?;
-
-    pixmap.fill_path(
-        &bg,
-        &colors.headerbar_paint(),
-        FillRule::Winding,
-        Transform::identity(),
-        None,
-    );
-
-    pixmap.fill_rect(
-        Rect::from_xywh(margin_h, h - 1.0, w, h)?,
-        &colors.border_paint(),
-        Transform::identity(),
-        None,
-    );
-
-    Some(())
-}
-
-fn rounded_headerbar_shape(x: f32, y: f32, width: f32, height: f32, radius: f32) -> Option<Path> {
-    use std::f32::consts::FRAC_1_SQRT_2;
-
-    let mut pb = PathBuilder::new();
-    let mut cursor = Point::from_xy(x, y);
-
-    // !!!
-    // This code is heavily "inspired" by https://gitlab.com/snakedye/snui/
-    // So technically it should be licensed under MPL-2.0, sorry about that 🥺 👉👈
-    // !!!
-
-    // Positioning the cursor
-    cursor.y += radius;
-    pb.move_to(cursor.x, cursor.y);
-
-    // Drawing the outline
-    pb.cubic_to(
-        cursor.x,
-        cursor.y,
-        cursor.x,
-        cursor.y - FRAC_1_SQRT_2 * radius,
-        {
-            cursor.x += radius;
-            cursor.x
-        },
-        {
-            cursor.y -= radius;
-            cursor.y
-        },
-    );
-    pb.line_to(
-        {
-            cursor.x = x + width - radius;
-            cursor.x
-        },
-        cursor.y,
-    );
-    pb.cubic_to(
-        cursor.x,
-        cursor.y,
-        cursor.x + FRAC_1_SQRT_2 * radius,
-        cursor.y,
-        {
-            cursor.x += radius;
-            cursor.x
-        },
-        {
-            cursor.y += radius;
-            cursor.y
-        },
-    );
-    pb.line_to(cursor.x, {
-        cursor.y = y + height;
-        cursor.y
-    });
-    pb.line_to(
-        {
-            cursor.x = x;
-            cursor.x
-        },
-        cursor.y,
-    );
-
-    pb.close();
-
-    pb.finish()
-}
@@ -0,0 +1,14 @@
+//! Logging through the `log` crate, compiled out without the `log` feature.
+#[cfg(feature = "log")]
+pub(crate) use log::{debug, error, warn};
+
+/// Type checks the message without logging it.
+#[cfg(not(feature = "log"))]
+macro_rules! discard {
+    ($($arg:tt)*) => {{
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+#[cfg(not(feature = "log"))]
+pub(crate) use {discard as debug, discard as error, discard as warn};
@@ -0,0 +1,71 @@
+//! Cost and damage of redrawing the decorations, for embedders profiling their frames.
+use std::time::Duration;
+
+use tiny_skia::IntRect;
+
+/// A decoration surface.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DecorationPart {
+    Header,
+    Left,
+    Right,
+    Bottom,
+}
+
+/// Area of a decoration surface updated by a redraw.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SurfaceDamage {
+    pub part: DecorationPart,
+    /// Damaged area in buffer pixels.
+    pub rect: IntRect,
+}
+
+/// What a single redraw of the decorations cost.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RedrawMetrics {
+    /// Time spent drawing and committing the decorations.
+    pub duration: Duration,
+    /// shm buffers drawn and attached to the decoration surfaces.
+    pub buffers: u32,
+    /// Bytes written into those buffers.
+    pub bytes_uploaded: usize,
+    /// Damage committed to each updated surface, surfaces left untouched are missing.
+    pub damage: Vec<SurfaceDamage>,
+}
+
+impl RedrawMetrics {
+    /// Starts accounting for a new redraw, keeping the allocation of `damage`.
+    pub(crate) fn reset(&mut self) {
+        self.duration = Duration::ZERO;
+        self.buffers = 0;
+        self.bytes_uploaded = 0;
+        self.damage.clear();
+    }
+
+    /// Accounts for an attached buffer of `width` × `height` pixels, of which `damage` changed.
+    pub(crate) fn record_buffer(
+        &mut self,
+        part: DecorationPart,
+        width: u32,
+        height: u32,
+        damage: IntRect,
+    ) {
+        self.buffers += 1;
+        self.bytes_uploaded += 4 * width as usize * height as usize;
+        self.damage.push(SurfaceDamage { part, rect: damage });
+    }
+}
+
+#[test]
+fn buffers_add_up() {
+    let mut metrics = RedrawMetrics::default();
+    let damage = IntRect::from_xywh(0, 0, 10, 1).unwrap();
+    metrics.record_buffer(DecorationPart::Header, 100, 10, damage);
+    metrics.record_buffer(DecorationPart::Left, 10, 1, damage);
+    assert_eq!(metrics.buffers, 2);
+    assert_eq!(metrics.bytes_uploaded, 4 * (1000 + 10));
+    assert_eq!(metrics.damage[1].part, DecorationPart::Left);
+
+    metrics.reset();
+    assert_eq!(metrics, RedrawMetrics::default());
+}
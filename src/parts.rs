@@ -1,66 +1,121 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use smithay_client_toolkit::{
-    reexports::client::{
-        protocol::{
-            wl_compositor::WlCompositor, wl_subcompositor::WlSubcompositor,
-            wl_subsurface::WlSubsurface, wl_surface::WlSurface,
+    reexports::{
+        client::{
+            protocol::{
+                wl_compositor::WlCompositor, wl_output::Transform,
+                wl_subcompositor::WlSubcompositor, wl_subsurface::WlSubsurface,
+                wl_surface::WlSurface,
+            },
+            Attached, DispatchData,
         },
-        Attached, DispatchData,
+        protocols::viewporter::client::{wp_viewport::WpViewport, wp_viewporter::WpViewporter},
     },
     window::FrameRequest,
 };
 
-use crate::{surface, Inner, Location};
+use crate::{dispatch, surface, DecorationPart, Inner, Location};
 
 pub enum DecorationPartKind {
     Header,
-    Top,
     Left,
     Right,
     Bottom,
     None,
 }
 
+impl DecorationPartKind {
+    pub fn part(self) -> Option<DecorationPart> {
+        match self {
+            DecorationPartKind::Header => Some(DecorationPart::Header),
+            DecorationPartKind::Left => Some(DecorationPart::Left),
+            DecorationPartKind::Right => Some(DecorationPart::Right),
+            DecorationPartKind::Bottom => Some(DecorationPart::Bottom),
+            DecorationPartKind::None => None,
+        }
+    }
+}
+
+/// How long hidden borders are kept around before their surfaces get destroyed.
+///
+/// Toggling maximized shouldn't recreate them every time, but windows which stay maximized
+/// don't need to keep three surfaces alive.
+const BORDER_DESTROY_DELAY: Duration = Duration::from_secs(10);
+
 #[derive(Debug)]
 pub struct Decoration {
     pub header: Part,
 
-    pub top: Part,
-    pub left: Part,
-    pub right: Part,
-    pub bottom: Part,
+    /// Only created once they are shown.
+    pub borders: Option<Borders>,
+    /// When the borders were hidden, `None` while they are shown.
+    borders_hidden_since: Option<Instant>,
 }
 
 impl Decoration {
-    pub fn iter(&self) -> [&Part; 5] {
-        [
-            &self.header,
-            &self.top,
-            &self.left,
-            &self.right,
-            &self.bottom,
-        ]
-    }
-
-    pub fn hide_decoration(&self) {
-        for p in self.iter() {
-            p.surface.attach(None, 0, 0);
-            p.surface.commit();
+    pub fn hide_decoration(&mut self, now: Instant) {
+        self.header.surface.attach(None, 0, 0);
+        self.header.surface.commit();
+        self.hide_borders(now);
+    }
+
+    /// Side borders are uniform along their length, with a viewporter they use a single row
+    /// of pixels stretched to the window height.
+    ///
+    /// The bottom border keeps its full buffer: a viewport stretches its whole source uniformly,
+    /// but the bottom border carries the shadow corners at both ends. Stretching only its middle
+    /// would take three subsurfaces in place of one.
+    fn set_viewporter(&mut self, viewporter: Option<&Attached<WpViewporter>>) {
+        if let Some(borders) = self.borders.as_mut() {
+            borders.left.set_viewport(viewporter);
+            borders.right.set_viewport(viewporter);
         }
     }
 
-    pub fn hide_borders(&self) {
-        for p in self.iter().iter().skip(1) {
-            p.surface.attach(None, 0, 0);
-            p.surface.commit();
+    /// Unmaps the borders, and destroys them once they have been hidden for a while.
+    pub fn hide_borders(&mut self, now: Instant) {
+        match self.borders_hidden_since {
+            Some(since) if now.duration_since(since) >= BORDER_DESTROY_DELAY => {
+                self.borders = None;
+            }
+            Some(_) => {}
+            None => {
+                if let Some(borders) = self.borders.as_ref() {
+                    for p in borders.iter() {
+                        p.surface.attach(None, 0, 0);
+                        p.surface.commit();
+                    }
+                }
+                self.borders_hidden_since = Some(now);
+            }
         }
     }
 }
 
+#[derive(Debug)]
+pub struct Borders {
+    pub left: Part,
+    pub right: Part,
+    pub bottom: Part,
+}
+
+impl Borders {
+    pub fn iter(&self) -> [&Part; 3] {
+        [&self.left, &self.right, &self.bottom]
+    }
+}
+
 #[derive(Default, Debug)]
 pub(crate) struct Parts {
     decoration: Option<Decoration>,
+    viewporter: Option<Attached<WpViewporter>>,
+    /// Buffer scale of all parts, instead of the one of the outputs they are on.
+    scale_override: Option<u32>,
 }
 
 impl Parts {
@@ -72,19 +127,74 @@ impl Parts {
         inner: Rc<RefCell<Inner>>,
     ) {
         if self.decoration.is_none() {
-            let header = Part::new(parent, compositor, subcompositor, Some(inner));
-            let top = Part::new(parent, compositor, subcompositor, None);
-            let left = Part::new(parent, compositor, subcompositor, None);
-            let right = Part::new(parent, compositor, subcompositor, None);
-            let bottom = Part::new(parent, compositor, subcompositor, None);
+            let mut header = Part::new(parent, compositor, subcompositor, inner);
+            header.set_scale_override(self.scale_override);
 
             self.decoration = Some(Decoration {
                 header,
-                top,
+                borders: None,
+                borders_hidden_since: Some(Instant::now()),
+            });
+        }
+    }
+
+    /// Creates the border surfaces if they don't exist yet.
+    pub fn show_borders(
+        &mut self,
+        parent: &WlSurface,
+        compositor: &Attached<WlCompositor>,
+        subcompositor: &Attached<WlSubcompositor>,
+        inner: Rc<RefCell<Inner>>,
+    ) {
+        let Some(decoration) = self.decoration.as_mut() else {
+            return;
+        };
+        decoration.borders_hidden_since = None;
+        if decoration.borders.is_none() {
+            let left = Part::new(parent, compositor, subcompositor, inner.clone());
+            let right = Part::new(parent, compositor, subcompositor, inner.clone());
+            let bottom = Part::new(parent, compositor, subcompositor, inner);
+
+            let mut borders = Borders {
                 left,
                 right,
                 bottom,
-            });
+            };
+            for part in [&mut borders.left, &mut borders.right, &mut borders.bottom] {
+                part.set_scale_override(self.scale_override);
+            }
+            decoration.borders = Some(borders);
+            decoration.set_viewporter(self.viewporter.as_ref());
+        }
+    }
+
+    pub fn hide_borders(&mut self, now: Instant) {
+        if let Some(decor) = self.decoration.as_mut() {
+            decor.hide_borders(now);
+        }
+    }
+
+    pub fn set_viewporter(&mut self, viewporter: Option<Attached<WpViewporter>>) {
+        self.viewporter = viewporter;
+        if let Some(decoration) = self.decoration.as_mut() {
+            decoration.set_viewporter(self.viewporter.as_ref());
+        }
+    }
+
+    pub fn scale_override(&self) -> Option<u32> {
+        self.scale_override
+    }
+
+    /// Applied with the next commit of the parts, which needs a buffer drawn for the new scale.
+    pub fn set_scale_override(&mut self, scale: Option<u32>) {
+        self.scale_override = scale;
+        if let Some(decoration) = self.decoration.as_mut() {
+            decoration.header.set_scale_override(scale);
+            if let Some(borders) = decoration.borders.as_mut() {
+                for part in [&mut borders.left, &mut borders.right, &mut borders.bottom] {
+                    part.set_scale_override(scale);
+                }
+            }
         }
     }
 
@@ -92,9 +202,9 @@ impl Parts {
         self.decoration = None;
     }
 
-    pub fn hide_decorations(&self) {
-        if let Some(decor) = self.decoration.as_ref() {
-            decor.hide_decoration();
+    pub fn hide_decorations(&mut self, now: Instant) {
+        if let Some(decor) = self.decoration.as_mut() {
+            decor.hide_decoration(now);
         }
     }
 
@@ -106,14 +216,16 @@ impl Parts {
         if let Some(decor) = self.decoration() {
             if surface.as_ref().equals(decor.header.surface.as_ref()) {
                 DecorationPartKind::Header
-            } else if surface.as_ref().equals(decor.top.surface.as_ref()) {
-                DecorationPartKind::Top
-            } else if surface.as_ref().equals(decor.bottom.surface.as_ref()) {
-                DecorationPartKind::Bottom
-            } else if surface.as_ref().equals(decor.left.surface.as_ref()) {
-                DecorationPartKind::Left
-            } else if surface.as_ref().equals(decor.right.surface.as_ref()) {
-                DecorationPartKind::Right
+            } else if let Some(borders) = decor.borders.as_ref() {
+                if surface.as_ref().equals(borders.bottom.surface.as_ref()) {
+                    DecorationPartKind::Bottom
+                } else if surface.as_ref().equals(borders.left.surface.as_ref()) {
+                    DecorationPartKind::Left
+                } else if surface.as_ref().equals(borders.right.surface.as_ref()) {
+                    DecorationPartKind::Right
+                } else {
+                    DecorationPartKind::None
+                }
             } else {
                 DecorationPartKind::None
             }
@@ -126,14 +238,16 @@ impl Parts {
         if let Some(decor) = self.decoration() {
             if surface.as_ref().equals(decor.header.surface.as_ref()) {
                 Location::Head
-            } else if surface.as_ref().equals(decor.top.surface.as_ref()) {
-                Location::Top
-            } else if surface.as_ref().equals(decor.bottom.surface.as_ref()) {
-                Location::Bottom
-            } else if surface.as_ref().equals(decor.left.surface.as_ref()) {
-                Location::Left
-            } else if surface.as_ref().equals(decor.right.surface.as_ref()) {
-                Location::Right
+            } else if let Some(borders) = decor.borders.as_ref() {
+                if surface.as_ref().equals(borders.bottom.surface.as_ref()) {
+                    Location::Bottom
+                } else if surface.as_ref().equals(borders.left.surface.as_ref()) {
+                    Location::Left
+                } else if surface.as_ref().equals(borders.right.surface.as_ref()) {
+                    Location::Right
+                } else {
+                    Location::None
+                }
             } else {
                 Location::None
             }
@@ -147,6 +261,8 @@ impl Parts {
 pub struct Part {
     pub surface: WlSurface,
     pub subsurface: WlSubsurface,
+    pub viewport: Option<WpViewport>,
+    scale_override: Option<u32>,
 }
 
 impl Part {
@@ -154,44 +270,75 @@ impl Part {
         parent: &WlSurface,
         compositor: &Attached<WlCompositor>,
         subcompositor: &Attached<WlSubcompositor>,
-        inner: Option<Rc<RefCell<Inner>>>,
+        inner: Rc<RefCell<Inner>>,
     ) -> Part {
-        let surface = if let Some(inner) = inner {
-            surface::setup_surface(
-                compositor.create_surface(),
-                Some(move |dpi, surface: WlSurface, ddata: DispatchData| {
-                    surface.set_buffer_scale(dpi);
-                    surface.commit();
-                    (inner.borrow_mut().implem)(FrameRequest::Refresh, 0, ddata);
-                }),
-            )
-        } else {
-            surface::setup_surface(
-                compositor.create_surface(),
-                Some(move |dpi, surface: WlSurface, _ddata: DispatchData| {
+        // The frame owns its parts, they must not keep it alive.
+        let inner = Rc::downgrade(&inner);
+        let surface = surface::setup_surface(
+            compositor.create_surface(),
+            Some(move |dpi, surface: WlSurface, ddata: DispatchData| {
+                let Some(inner) = inner.upgrade() else {
+                    return;
+                };
+                let mut guard = inner.borrow_mut();
+                // An overridden scale doesn't follow the outputs, the transform still does.
+                if guard.parts.scale_override().is_none() {
+                    // Applied with the next commit, together with a buffer drawn for the new
+                    // scale.
                     surface.set_buffer_scale(dpi);
-                    surface.commit();
-                }),
-            )
-        };
+                }
+                guard.dirty = true;
+                guard.request(FrameRequest::Refresh, 0);
+                drop(guard);
+                dispatch(&inner, ddata);
+            }),
+        );
 
         let surface = surface.detach();
 
         let subsurface = subcompositor.get_subsurface(&surface, parent);
+        // Subsurfaces start out synchronized, this is only made explicit: our commits are
+        // applied together with the next commit of the window, so decorations and content
+        // always change in the same frame.
+        subsurface.set_sync();
 
         Part {
             surface,
             subsurface: subsurface.detach(),
+            viewport: None,
+            scale_override: None,
         }
     }
 
+    fn set_viewport(&mut self, viewporter: Option<&Attached<WpViewporter>>) {
+        if let Some(viewport) = self.viewport.take() {
+            viewport.destroy();
+        }
+        self.viewport = viewporter.map(|v| v.get_viewport(&self.surface).detach());
+    }
+
     pub fn scale(&self) -> u32 {
-        surface::get_surface_scale_factor(&self.surface) as u32
+        self.scale_override
+            .unwrap_or_else(|| surface::get_surface_scale_factor(&self.surface) as u32)
+    }
+
+    fn set_scale_override(&mut self, scale: Option<u32>) {
+        if scale != self.scale_override {
+            self.scale_override = scale;
+            self.surface.set_buffer_scale(self.scale() as i32);
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        surface::get_surface_transform(&self.surface)
     }
 }
 
 impl Drop for Part {
     fn drop(&mut self) {
+        if let Some(viewport) = self.viewport.take() {
+            viewport.destroy();
+        }
         self.subsurface.destroy();
         self.surface.destroy();
     }
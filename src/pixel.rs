@@ -0,0 +1,160 @@
+//! Conversion of tiny-skia pixels into shm buffer formats.
+use smithay_client_toolkit::reexports::client::protocol::wl_shm;
+
+use crate::logging::warn;
+
+/// Pixel format of the decoration buffers.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum PixelFormat {
+    #[default]
+    Argb8888,
+    /// 10 bits per color channel, with alpha reduced to 2 bits.
+    Argb2101010,
+    /// 10 bits per color channel without alpha, transparent areas turn black.
+    Xrgb2101010,
+}
+
+impl PixelFormat {
+    /// Picks `preferred` if the compositor supports it, `Argb8888` otherwise.
+    ///
+    /// `Argb8888` support is mandatory for compositors, so it is always a safe fallback.
+    pub fn negotiate(preferred: PixelFormat, supported: &[wl_shm::Format]) -> PixelFormat {
+        if supported.contains(&preferred.shm_format()) {
+            preferred
+        } else {
+            if preferred != PixelFormat::Argb8888 {
+                warn!(
+                    "{:?} is not supported by the compositor, falling back to Argb8888",
+                    preferred
+                );
+            }
+            PixelFormat::Argb8888
+        }
+    }
+
+    pub fn shm_format(self) -> wl_shm::Format {
+        match self {
+            PixelFormat::Argb8888 => wl_shm::Format::Argb8888,
+            PixelFormat::Argb2101010 => wl_shm::Format::Argb2101010,
+            PixelFormat::Xrgb2101010 => wl_shm::Format::Xrgb2101010,
+        }
+    }
+
+    /// Converts premultiplied RGBA pixels, as drawn by tiny-skia, into this format in place.
+    ///
+    /// shm formats are little-endian packed words, so tiny-skia's byte order never matches them,
+    /// regardless of the host endianness.
+    pub fn convert(self, pixels: &mut [u8]) {
+        match self {
+            PixelFormat::Argb8888 => {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]];
+                    let argb = u32::from_be_bytes([a, r, g, b]);
+                    pixel.copy_from_slice(&argb.to_le_bytes());
+                }
+            }
+            PixelFormat::Argb2101010 => {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]];
+                    // Rounding alpha changes the coverage, colors have to be premultiplied
+                    // with the reduced alpha again to stay within it.
+                    let a2 = (u32::from(a) * 3 + 127) / 255;
+                    let channel = |c: u8| match a {
+                        0 => 0,
+                        _ => (u32::from(c) * 1023 * a2 + 3 * u32::from(a) / 2) / (3 * u32::from(a)),
+                    };
+                    let argb = a2 << 30 | channel(r) << 20 | channel(g) << 10 | channel(b);
+                    pixel.copy_from_slice(&argb.to_le_bytes());
+                }
+            }
+            PixelFormat::Xrgb2101010 => {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(widen_10);
+                    let xrgb = 0b11 << 30 | r << 20 | g << 10 | b;
+                    pixel.copy_from_slice(&xrgb.to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// Fades premultiplied RGBA pixels, colors are scaled along with the alpha.
+pub(crate) fn apply_opacity(pixels: &mut [u8], opacity: f32) {
+    if opacity >= 1.0 {
+        return;
+    }
+    let opacity = (opacity.max(0.0) * 256.0) as u32;
+    for byte in pixels {
+        *byte = ((u32::from(*byte) * opacity) >> 8) as u8;
+    }
+}
+
+/// Expands an 8-bit channel to 10 bits, mapping 255 to 1023.
+fn widen_10(c: u8) -> u32 {
+    let c = u32::from(c);
+    c << 2 | c >> 6
+}
+
+#[test]
+fn argb8888_byte_order() {
+    let mut pixels = [255, 0, 0, 255, 0, 0, 128, 128];
+    PixelFormat::Argb8888.convert(&mut pixels);
+    assert_eq!(pixels, [0, 0, 255, 255, 128, 0, 0, 128]);
+}
+
+#[test]
+fn opacity_keeps_pixels_premultiplied() {
+    let mut pixels = [200, 100, 0, 200];
+    apply_opacity(&mut pixels, 1.0);
+    assert_eq!(pixels, [200, 100, 0, 200]);
+    apply_opacity(&mut pixels, 0.5);
+    assert_eq!(pixels, [100, 50, 0, 100]);
+    apply_opacity(&mut pixels, 0.0);
+    assert_eq!(pixels, [0; 4]);
+}
+
+#[test]
+fn negotiate_falls_back_to_argb8888() {
+    let supported = [wl_shm::Format::Argb8888, wl_shm::Format::Xrgb8888];
+    assert_eq!(
+        PixelFormat::negotiate(PixelFormat::Argb2101010, &supported),
+        PixelFormat::Argb8888
+    );
+    let supported = [wl_shm::Format::Argb2101010, wl_shm::Format::Argb8888];
+    assert_eq!(
+        PixelFormat::negotiate(PixelFormat::Argb2101010, &supported),
+        PixelFormat::Argb2101010
+    );
+}
+
+#[test]
+fn argb2101010_opaque() {
+    let mut pixels = [255, 128, 0, 255];
+    PixelFormat::Argb2101010.convert(&mut pixels);
+    let argb = u32::from_le_bytes(pixels);
+    assert_eq!(argb >> 30, 0b11);
+    assert_eq!(argb >> 20 & 0x3ff, 1023);
+    assert_eq!(argb >> 10 & 0x3ff, widen_10(128));
+    assert_eq!(argb & 0x3ff, 0);
+}
+
+#[test]
+fn argb2101010_stays_premultiplied() {
+    // Half transparent white, alpha rounds up to 2/3.
+    let mut pixels = [128, 128, 128, 128];
+    PixelFormat::Argb2101010.convert(&mut pixels);
+    let argb = u32::from_le_bytes(pixels);
+    assert_eq!(argb >> 30, 2);
+    assert_eq!(argb >> 20 & 0x3ff, 682);
+
+    let mut pixels = [0, 0, 0, 0];
+    PixelFormat::Argb2101010.convert(&mut pixels);
+    assert_eq!(pixels, [0; 4]);
+}
+
+#[test]
+fn xrgb2101010_drops_alpha() {
+    let mut pixels = [0, 0, 255, 0];
+    PixelFormat::Xrgb2101010.convert(&mut pixels);
+    assert_eq!(u32::from_le_bytes(pixels), 0b11 << 30 | 1023);
+}
@@ -0,0 +1,122 @@
+//! Shm pool of the decorations.
+use std::{
+    io,
+    ops::{Deref, DerefMut},
+};
+
+use smithay_client_toolkit::{
+    reexports::client::{protocol::wl_shm::WlShm, Attached},
+    shm::AutoMemPool,
+};
+
+use crate::logging::{debug, warn};
+
+/// Pools that grew beyond this are trimmed once the decorations got a lot smaller.
+const TRIM_THRESHOLD: usize = 4 * 1024 * 1024;
+
+/// `AutoMemPool` that gets replaced once it is far larger than the decorations need.
+///
+/// shm pools can only grow, so after a window went from fullscreen on a 4K output back to a small
+/// size, the pool would keep all that memory mapped otherwise. Buffers still in use by the
+/// compositor stay valid when their pool is destroyed.
+///
+/// A pool that failed to hand out a buffer is replaced as well, its mapping may be left in an
+/// unusable state.
+///
+/// The pool may be shared by several frames, each identified by a key.
+#[derive(Debug)]
+pub struct Pool {
+    pool: AutoMemPool,
+    shm: Attached<WlShm>,
+    /// Memory needed by each frame for its last redraw.
+    needs: Vec<(usize, usize)>,
+    /// Most memory needed at once since the pool was created.
+    peak: usize,
+    /// Set by [`Pool::failed`], until the pool was recreated.
+    failed: bool,
+}
+
+impl Pool {
+    pub fn new(shm: Attached<WlShm>) -> io::Result<Self> {
+        Ok(Self {
+            pool: AutoMemPool::new(shm.clone())?,
+            shm,
+            needs: Vec::new(),
+            peak: 0,
+            failed: false,
+        })
+    }
+
+    /// Records that the next redraw of `frame` needs `needed` bytes, recreating the pool if it
+    /// got too big or failed.
+    pub fn trim(&mut self, frame: usize, needed: usize) {
+        match self.needs.iter_mut().find(|(key, _)| *key == frame) {
+            Some((_, need)) => *need = needed,
+            None => self.needs.push((frame, needed)),
+        }
+
+        let needed = self
+            .needs
+            .iter()
+            .fold(0usize, |sum, (_, need)| sum.saturating_add(*need));
+        self.peak = self.peak.max(needed);
+        if !self.failed && !should_trim(self.peak, needed) {
+            return;
+        }
+
+        debug!(
+            "Recreating the decoration shm pool, {} of {} bytes are needed",
+            needed, self.peak
+        );
+        match AutoMemPool::new(self.shm.clone()) {
+            Ok(pool) => {
+                self.pool = pool;
+                self.peak = needed;
+                self.failed = false;
+            }
+            // Tried again by the next redraw if the pool failed.
+            Err(err) => warn!("Failed to recreate the decoration shm pool: {}", err),
+        }
+    }
+
+    /// Replaces the pool on the next [`Pool::trim`], after creating a buffer in it failed.
+    pub fn failed(&mut self) {
+        self.failed = true;
+    }
+
+    /// Stops accounting for a frame that was dropped or moved to another pool.
+    pub fn forget(&mut self, frame: usize) {
+        self.needs.retain(|(key, _)| *key != frame);
+    }
+}
+
+impl Deref for Pool {
+    type Target = AutoMemPool;
+
+    fn deref(&self) -> &AutoMemPool {
+        &self.pool
+    }
+}
+
+impl DerefMut for Pool {
+    fn deref_mut(&mut self) -> &mut AutoMemPool {
+        &mut self.pool
+    }
+}
+
+fn should_trim(peak: usize, needed: usize) -> bool {
+    peak >= TRIM_THRESHOLD && needed * 4 <= peak
+}
+
+#[test]
+fn trim_after_shrinking() {
+    let peak = 32 * 1024 * 1024;
+    assert!(!should_trim(peak, peak / 2));
+    assert!(should_trim(peak, peak / 4));
+    assert!(should_trim(peak, 0));
+}
+
+#[test]
+fn keep_small_pools() {
+    assert!(!should_trim(TRIM_THRESHOLD / 2, 0));
+}
@@ -0,0 +1,121 @@
+//! Soft drop shadow around floating windows.
+//!
+//! Compositors usually leave shadows to clients drawing their own decorations, so the shadow is
+//! drawn into the otherwise transparent resize margins around the window.
+use tiny_skia::{PixmapMut, PremultipliedColorU8};
+
+use crate::theme::Sizes;
+
+/// Opacity of the shadow right at the window edge.
+const SHADOW_ALPHA: f32 = 0.25;
+
+/// Shadow of a window, as seen by one decoration part.
+#[derive(Debug, Copy, Clone)]
+pub struct Shadow {
+    /// Logical position of the part relative to the window content.
+    pub origin: (f32, f32),
+    /// Logical size of the window content.
+    pub window: (u32, u32),
+    pub scale: u32,
+    /// Logical radius of the rounded header corners.
+    pub corner_radius: f32,
+    /// The header is drawn above the content, the shadow fades out across the border.
+    pub sizes: Sizes,
+}
+
+impl Shadow {
+    /// Draws the shadow, the window itself is left out.
+    pub fn draw(&self, pixmap: &mut PixmapMut) {
+        let width = pixmap.width();
+        let scale = self.scale as f32;
+        for (id, pixel) in pixmap.pixels_mut().iter_mut().enumerate() {
+            let x = self.origin.0 + (id as u32 % width) as f32 / scale + 0.5 / scale;
+            let y = self.origin.1 + (id as u32 / width) as f32 / scale + 0.5 / scale;
+
+            let alpha = shadow_alpha(self.distance(x, y), self.sizes.border);
+            if alpha > 0.0 {
+                let alpha = (alpha * 255.0).round() as u8;
+                if let Some(color) = PremultipliedColorU8::from_rgba(0, 0, 0, alpha) {
+                    *pixel = color;
+                }
+            }
+        }
+    }
+
+    /// Distance of a logical point to the window outline, `0` inside of the window.
+    fn distance(&self, x: f32, y: f32) -> f32 {
+        let (left, right) = (0.0, self.window.0 as f32);
+        let (top, bottom) = (-(self.sizes.header as f32), self.window.1 as f32);
+
+        // Rounded top corners of the header, clamped to it like in `rounded_headerbar_shape`.
+        let radius = self
+            .corner_radius
+            .min(right.min(self.sizes.header as f32) / 2.0);
+        let corner_y = top + radius;
+        if y < corner_y {
+            let corner_x = if x < left + radius {
+                Some(left + radius)
+            } else if x > right - radius {
+                Some(right - radius)
+            } else {
+                None
+            };
+
+            if let Some(corner_x) = corner_x {
+                let distance = (x - corner_x).hypot(y - corner_y) - radius;
+                return distance.max(0.0);
+            }
+        }
+
+        let dx = (left - x).max(x - right).max(0.0);
+        let dy = (top - y).max(y - bottom).max(0.0);
+        dx.hypot(dy)
+    }
+}
+
+/// Falls off from the window edge to the end of the resize margin, `border` wide.
+fn shadow_alpha(distance: f32, border: u32) -> f32 {
+    let border = border as f32;
+    if distance <= 0.0 || distance >= border {
+        0.0
+    } else {
+        SHADOW_ALPHA * (1.0 - distance / border).powi(2)
+    }
+}
+
+#[test]
+fn shadow_fades_out() {
+    let border = crate::theme::BORDER_SIZE;
+    assert_eq!(shadow_alpha(0.0, border), 0.0);
+    assert!(shadow_alpha(0.5, border) > shadow_alpha(5.0, border));
+    assert!(shadow_alpha(5.0, border) > 0.0);
+    assert_eq!(shadow_alpha(border as f32, border), 0.0);
+    assert!(shadow_alpha(15.0, 20) > 0.0);
+}
+
+#[test]
+fn shadow_distance_follows_corners() {
+    let shadow = Shadow {
+        origin: (0.0, 0.0),
+        window: (100, 100),
+        scale: 1,
+        corner_radius: crate::theme::CORNER_RADIUS,
+        sizes: Sizes::default(),
+    };
+    assert_eq!(shadow.distance(50.0, 50.0), 0.0);
+    assert_eq!(shadow.distance(-3.0, 50.0), 3.0);
+    assert_eq!(shadow.distance(50.0, 104.0), 4.0);
+    // Square bottom corners, rounded top ones.
+    assert_eq!(shadow.distance(-3.0, 104.0), 5.0);
+    let top = -(crate::theme::HEADER_SIZE as f32);
+    assert!(shadow.distance(0.0, top) > 0.0);
+    assert_eq!(shadow.distance(50.0, top - 2.0), 2.0);
+
+    // Narrower than both corners, they meet in the middle.
+    let narrow = Shadow {
+        window: (10, 100),
+        ..shadow
+    };
+    assert!(narrow.distance(0.0, top) > 0.0);
+    assert_eq!(narrow.distance(5.0, top - 1.0), 1.0);
+}
@@ -11,13 +11,20 @@ use sctk::output::{add_output_listener, with_output_info, OutputListener};
 
 pub(crate) struct SurfaceUserData {
     scale_factor: i32,
-    outputs: Vec<(wl_output::WlOutput, i32, OutputListener)>,
+    transform: wl_output::Transform,
+    outputs: Vec<(
+        wl_output::WlOutput,
+        i32,
+        wl_output::Transform,
+        OutputListener,
+    )>,
 }
 
 impl SurfaceUserData {
     fn new() -> Self {
         SurfaceUserData {
             scale_factor: 1,
+            transform: wl_output::Transform::Normal,
             outputs: Vec::new(),
         }
     }
@@ -30,7 +37,9 @@ impl SurfaceUserData {
     ) where
         F: FnMut(i32, wl_surface::WlSurface, DispatchData) + 'static,
     {
-        let output_scale = with_output_info(&output, |info| info.scale_factor).unwrap_or(1);
+        let (output_scale, output_transform) =
+            with_output_info(&output, |info| (info.scale_factor, info.transform))
+                .unwrap_or((1, wl_output::Transform::Normal));
         let my_surface = surface.clone();
         // Use a UserData to safely share the callback with the other thread
         let my_callback = client::UserData::new();
@@ -45,40 +54,46 @@ impl SurfaceUserData {
                 .unwrap()
                 .lock()
                 .unwrap();
-            // update the scale factor of the relevant output
-            for (ref o, ref mut factor, _) in user_data.outputs.iter_mut() {
+            // update the scale factor and transform of the relevant output
+            for (ref o, ref mut factor, ref mut transform, _) in user_data.outputs.iter_mut() {
                 if o.as_ref().equals(output.as_ref()) {
                     if info.obsolete {
                         // an output that no longer exists is marked by a scale factor of -1
                         *factor = -1;
                     } else {
                         *factor = info.scale_factor;
+                        *transform = info.transform;
                     }
                     break;
                 }
             }
             // recompute the scale factor with the new info
             let callback = my_callback.get::<Rc<RefCell<F>>>().cloned();
-            let old_scale_factor = user_data.scale_factor;
+            let old = (user_data.scale_factor, user_data.transform);
             let new_scale_factor = user_data.recompute_scale_factor();
+            let changed = old != (new_scale_factor, user_data.transform);
             drop(user_data);
             if let Some(ref cb) = callback {
-                if old_scale_factor != new_scale_factor {
+                if changed {
                     (*cb.borrow_mut())(new_scale_factor, surface.clone(), ddata);
                 }
             }
         });
-        self.outputs.push((output, output_scale, listener));
+        self.outputs
+            .push((output, output_scale, output_transform, listener));
     }
 
     pub(crate) fn leave(&mut self, output: &wl_output::WlOutput) {
         self.outputs
-            .retain(|(ref output2, _, _)| !output.as_ref().equals(output2.as_ref()));
+            .retain(|(ref output2, _, _, _)| !output.as_ref().equals(output2.as_ref()));
     }
 
+    /// Recomputes the scale factor and transform, returns the new scale factor.
+    ///
+    /// The transform is only adopted if all outputs of the surface share it.
     fn recompute_scale_factor(&mut self) -> i32 {
         let mut new_scale_factor = 1;
-        self.outputs.retain(|&(_, output_scale, _)| {
+        self.outputs.retain(|&(_, output_scale, _, _)| {
             if output_scale > 0 {
                 new_scale_factor = ::std::cmp::max(new_scale_factor, output_scale);
                 true
@@ -92,6 +107,12 @@ impl SurfaceUserData {
             return self.scale_factor;
         }
         self.scale_factor = new_scale_factor;
+        let transform = self.outputs[0].2;
+        self.transform = if self.outputs.iter().all(|o| o.2 == transform) {
+            transform
+        } else {
+            wl_output::Transform::Normal
+        };
         new_scale_factor
     }
 }
@@ -122,11 +143,12 @@ where
             }
             _ => unreachable!(),
         };
-        let old_scale_factor = user_data.scale_factor;
+        let old = (user_data.scale_factor, user_data.transform);
         let new_scale_factor = user_data.recompute_scale_factor();
+        let changed = old != (new_scale_factor, user_data.transform);
         drop(user_data);
         if let Some(ref cb) = callback {
-            if old_scale_factor != new_scale_factor {
+            if changed {
                 (*cb.borrow_mut())(new_scale_factor, surface.detach(), ddata);
             }
         }
@@ -152,3 +174,24 @@ pub fn get_surface_scale_factor(surface: &wl_surface::WlSurface) -> i32 {
         .unwrap()
         .scale_factor
 }
+
+/// Returns the transform of the outputs the surface is displayed on.
+pub fn get_surface_transform(surface: &wl_surface::WlSurface) -> wl_output::Transform {
+    surface
+        .as_ref()
+        .user_data()
+        .get::<Mutex<SurfaceUserData>>()
+        .expect("SCTK: Surface was not created by SCTK.")
+        .lock()
+        .unwrap()
+        .transform
+}
+
+/// Returns `true` if the surface was set up using [`setup_surface`].
+pub fn is_decoration_surface(surface: &wl_surface::WlSurface) -> bool {
+    surface
+        .as_ref()
+        .user_data()
+        .get::<Mutex<SurfaceUserData>>()
+        .is_some()
+}
@@ -0,0 +1,154 @@
+//! Synthetic pointer input for tests of the decorations without a compositor.
+//!
+//! A [`PointerSimulator`] runs the frame's own pointer handlers on a window described by a
+//! [`RenderState`], and returns what they ask of the frame: its [`FrameRequest`]s, without the
+//! seat, and the calls into the [`AdwaitaConfig`] callbacks.
+//!
+//! [`FrameRequest`]: smithay_client_toolkit::window::FrameRequest
+//! [`AdwaitaConfig`]: crate::AdwaitaConfig
+use smithay_client_toolkit::reexports::client::protocol::wl_pointer;
+
+use crate::{
+    core::surface_at,
+    input::{PointerFrame, PointerInput, PointerState},
+    ButtonKind, ButtonScroll, Location, LogicalRect, RenderState,
+};
+
+pub use crate::{core::PointerRequest, input::PointerOutput};
+
+/// `BTN_LEFT` of the button events.
+pub const BUTTON_LEFT: u32 = 0x110;
+/// `BTN_RIGHT` of the button events.
+pub const BUTTON_RIGHT: u32 = 0x111;
+
+/// A pointer over a window, moved in logical pixels from the top left corner of its decorations
+/// like in [`hit_test`](crate::hit_test).
+///
+/// Moving onto another surface of the window leaves the previous one and enters the new one,
+/// like a compositor would.
+#[derive(Debug)]
+pub struct PointerSimulator {
+    /// The window, changes apply to the following input.
+    pub state: RenderState,
+    /// See [`AdwaitaConfig::drag_regions`](crate::AdwaitaConfig::drag_regions).
+    pub drag_regions: Vec<LogicalRect>,
+    /// See [`AdwaitaConfig::button_scroll`](crate::AdwaitaConfig::button_scroll).
+    pub button_scroll: ButtonScroll,
+    /// Whether an [`AdwaitaConfig::axis_maximize`](crate::AdwaitaConfig::axis_maximize)
+    /// callback is set.
+    pub axis_maximize: bool,
+    /// Whether an [`AdwaitaConfig::titlebar_axis`](crate::AdwaitaConfig::titlebar_axis)
+    /// callback is set.
+    pub titlebar_axis: bool,
+    pointer: PointerState,
+    /// The surface entered, `None` while the pointer is outside of the window.
+    surface: Option<Location>,
+}
+
+impl PointerSimulator {
+    /// A pointer outside of the window.
+    pub fn new(state: RenderState) -> Self {
+        Self {
+            state,
+            drag_regions: Vec::new(),
+            button_scroll: ButtonScroll::default(),
+            axis_maximize: false,
+            titlebar_axis: false,
+            pointer: PointerState::new(),
+            surface: None,
+        }
+    }
+
+    /// Moves the pointer to `(x, y)`, entering the window if it was outside.
+    pub fn motion(&mut self, x: f64, y: f64) -> Vec<PointerOutput> {
+        let buttons = self.state.buttons();
+        let target = surface_at(&self.state, &buttons, x, y);
+
+        let mut inputs = Vec::new();
+        match (self.surface, target) {
+            (Some(old), Some((new, x, y))) if old == new => {
+                inputs.push(PointerInput::Motion { x, y });
+            }
+            (old, target) => {
+                if old.is_some() {
+                    inputs.push(PointerInput::Leave);
+                }
+                if let Some((surface, x, y)) = target {
+                    inputs.push(PointerInput::Enter {
+                        surface,
+                        on_content: surface == Location::None,
+                        x,
+                        y,
+                    });
+                }
+            }
+        }
+        self.surface = target.map(|(surface, ..)| surface);
+        self.input(inputs)
+    }
+
+    /// Moves the pointer out of the window.
+    pub fn leave(&mut self) -> Vec<PointerOutput> {
+        match self.surface.take() {
+            Some(_) => self.input(vec![PointerInput::Leave]),
+            None => Vec::new(),
+        }
+    }
+
+    /// Presses `button`, `time` in milliseconds tells double clicks apart.
+    pub fn press(&mut self, button: u32, time: u32) -> Vec<PointerOutput> {
+        self.button(button, time, true)
+    }
+
+    pub fn release(&mut self, button: u32, time: u32) -> Vec<PointerOutput> {
+        self.button(button, time, false)
+    }
+
+    /// Presses and releases the left mouse button.
+    pub fn click(&mut self, time: u32) -> Vec<PointerOutput> {
+        let mut output = self.press(BUTTON_LEFT, time);
+        output.extend(self.release(BUTTON_LEFT, time));
+        output
+    }
+
+    pub fn axis(&mut self, axis: wl_pointer::Axis, value: f64) -> Vec<PointerOutput> {
+        self.input(vec![PointerInput::Axis { axis, value }])
+    }
+
+    /// The button under the pointer, drawn hovered by the frame.
+    pub fn hovered(&self) -> Option<ButtonKind> {
+        match self.pointer.location {
+            Location::Button(kind) => Some(kind),
+            _ => None,
+        }
+    }
+
+    fn button(&mut self, button: u32, time: u32, pressed: bool) -> Vec<PointerOutput> {
+        // Without a surface the events go to another client.
+        if self.surface.is_none() {
+            return Vec::new();
+        }
+        self.input(vec![PointerInput::Button {
+            time,
+            button,
+            pressed,
+        }])
+    }
+
+    fn input(&mut self, inputs: Vec<PointerInput>) -> Vec<PointerOutput> {
+        let buttons = self.state.buttons();
+        let frame = PointerFrame {
+            axis_maximize: self.axis_maximize,
+            titlebar_axis: self.titlebar_axis,
+            button_scroll: self.button_scroll,
+            drag_regions: &self.drag_regions,
+            ..PointerFrame::new(&self.state, &buttons)
+        };
+
+        let mut output = Vec::new();
+        for input in inputs {
+            self.pointer.input(input, &frame, &mut output);
+        }
+        output
+    }
+}
@@ -5,6 +5,25 @@ use tiny_skia::{Paint, Shader};
 
 pub(crate) const BORDER_SIZE: u32 = 10;
 pub(crate) const HEADER_SIZE: u32 = 35;
+/// Radius of the rounded header corners.
+pub(crate) const CORNER_RADIUS: f32 = 10.0;
+
+/// Height of the header and width of the borders in logical pixels, the defaults are
+/// [`HEADER_SIZE`] and [`BORDER_SIZE`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Sizes {
+    pub header: u32,
+    pub border: u32,
+}
+
+impl Default for Sizes {
+    fn default() -> Self {
+        Self {
+            header: HEADER_SIZE,
+            border: BORDER_SIZE,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct ColorMap {
@@ -17,7 +36,19 @@ pub struct ColorMap {
 }
 
 impl ColorMap {
-    pub(crate) fn headerbar_paint(&self) -> Paint {
+    /// Blends from `self` to `other` as `t` goes from `0.0` to `1.0`.
+    pub(crate) fn mix(&self, other: &ColorMap, t: f32) -> ColorMap {
+        ColorMap {
+            headerbar: mix(self.headerbar, other.headerbar, t),
+            button_idle: mix(self.button_idle, other.button_idle, t),
+            button_hover: mix(self.button_hover, other.button_hover, t),
+            button_icon: mix(self.button_icon, other.button_icon, t),
+            border_color: mix(self.border_color, other.border_color, t),
+            font_color: mix(self.font_color, other.font_color, t),
+        }
+    }
+
+    pub(crate) fn headerbar_paint(&self) -> Paint<'_> {
         Paint {
             shader: Shader::SolidColor(self.headerbar),
             anti_alias: true,
@@ -25,7 +56,7 @@ impl ColorMap {
         }
     }
 
-    pub(crate) fn button_idle_paint(&self) -> Paint {
+    pub(crate) fn button_idle_paint(&self) -> Paint<'_> {
         Paint {
             shader: Shader::SolidColor(self.button_idle),
             anti_alias: true,
@@ -33,7 +64,7 @@ impl ColorMap {
         }
     }
 
-    pub(crate) fn button_hover_paint(&self) -> Paint {
+    pub(crate) fn button_hover_paint(&self) -> Paint<'_> {
         Paint {
             shader: Shader::SolidColor(self.button_hover),
             anti_alias: true,
@@ -41,14 +72,14 @@ impl ColorMap {
         }
     }
 
-    pub(crate) fn button_icon_paint(&self) -> Paint {
+    pub(crate) fn button_icon_paint(&self) -> Paint<'_> {
         Paint {
             shader: Shader::SolidColor(self.button_icon),
             ..Default::default()
         }
     }
 
-    pub(crate) fn border_paint(&self) -> Paint {
+    pub(crate) fn border_paint(&self) -> Paint<'_> {
         Paint {
             shader: Shader::SolidColor(self.border_color),
             ..Default::default()
@@ -56,6 +87,17 @@ impl ColorMap {
     }
 }
 
+fn mix(from: Color, to: Color, t: f32) -> Color {
+    let channel = |from: f32, to: f32| from + (to - from) * t;
+    Color::from_rgba(
+        channel(from.red(), to.red()),
+        channel(from.green(), to.green()),
+        channel(from.blue(), to.blue()),
+        channel(from.alpha(), to.alpha()),
+    )
+    .unwrap_or(to)
+}
+
 #[derive(Debug, Clone)]
 pub struct ColorTheme {
     pub active: ColorMap,
@@ -73,7 +115,12 @@ impl ColorTheme {
     /// * dbus org.freedesktop.portal.Settings
     ///   <https://flatpak.github.io/xdg-desktop-portal/#gdbus-interface-org-freedesktop-portal-Settings>
     pub fn auto() -> Self {
-        match crate::config::prefer_dark() {
+        let dark = crate::config::prefer_dark().value;
+        crate::logging::debug!(
+            "Detected {} color scheme",
+            if dark { "dark" } else { "light" }
+        );
+        match dark {
             true => Self::dark(),
             false => Self::light(),
         }
@@ -124,10 +171,23 @@ impl ColorTheme {
 
 impl ColorTheme {
     pub(crate) fn for_state(&self, state: WindowState) -> &ColorMap {
-        if state == WindowState::Active {
+        self.colors(state == WindowState::Active)
+    }
+
+    pub(crate) fn colors(&self, active: bool) -> &ColorMap {
+        if active {
             &self.active
         } else {
             &self.inactive
         }
     }
 }
+
+#[test]
+fn mix_colors() {
+    let from = Color::from_rgba8(0, 0, 0, 255);
+    let to = Color::from_rgba8(255, 255, 255, 255);
+    assert_eq!(mix(from, to, 0.0), from);
+    assert_eq!(mix(from, to, 1.0), to);
+    assert_eq!(mix(from, to, 0.5).red(), 0.5);
+}
@@ -25,21 +25,23 @@ pub struct TitleText {
 }
 
 impl TitleText {
-    pub fn new(color: Color) -> Option<Self> {
+    /// Title drawn with `font`, a description like `Cantarell Bold 11`, or the system titlebar
+    /// font.
+    pub fn new(color: Color, font: Option<&str>) -> Option<Self> {
         #[cfg(feature = "crossfont")]
-        return crossfont_renderer::CrossfontTitleText::new(color)
+        return crossfont_renderer::CrossfontTitleText::new(color, font)
             .ok()
             .map(|imp| Self { imp });
 
         #[cfg(all(not(feature = "crossfont"), feature = "ab_glyph"))]
         return Some(Self {
-            imp: ab_glyph_renderer::AbGlyphTitleText::new(color),
+            imp: ab_glyph_renderer::AbGlyphTitleText::new(color, font),
         });
 
         #[cfg(all(not(feature = "crossfont"), not(feature = "ab_glyph")))]
         {
-            let _ = color;
-            return None;
+            let _ = (color, font);
+            None
         }
     }
 
@@ -1,9 +1,13 @@
 //! Title renderer using ab_glyph & Cantarell-Regular.ttf (SIL Open Font Licence v1.1).
 //!
 //! Uses embedded font & requires no dynamically linked dependencies.
-use crate::title::{config, font_preference::FontPreference};
+use crate::{
+    logging::debug,
+    title::{config, font_preference::FontPreference},
+};
 use ab_glyph::{point, Font, FontArc, FontVec, Glyph, PxScale, ScaleFont, VariableFont};
 use std::{
+    cell::RefCell,
     fs::File,
     io::{BufReader, Read},
     process::Command,
@@ -23,27 +27,14 @@ pub struct AbGlyphTitleText {
 }
 
 impl AbGlyphTitleText {
-    pub fn new(color: Color) -> Self {
-        let font_pref = config::titlebar_font().unwrap_or_default();
-        let font = font_file_matching(&font_pref)
-            .and_then(read_to_vec)
-            .and_then(|data| {
-                let mut font = FontVec::try_from_vec(data).ok()?;
-                // basic "bold" handling for variable fonts
-                if font_pref
-                    .style
-                    .map_or(false, |s| s.eq_ignore_ascii_case("bold"))
-                {
-                    font.set_variation(b"wght", 700.0);
-                }
-                Some(FontArc::from(font))
-            })
-            // fallback to using embedded font if system font doesn't work
-            .unwrap_or_else(|| FontArc::try_from_slice(CANTARELL).unwrap());
+    pub fn new(color: Color, font: Option<&str>) -> Self {
+        let font_pref = config::font_preference(font);
+        let font = cached_font(&font_pref);
 
+        // Fonts without units per em aren't loaded, the fallback is 1pt = 4/3px at 96 dpi.
         let size = font
             .pt_to_px_scale(font_pref.pt_size)
-            .expect("invalid font units_per_em");
+            .unwrap_or(PxScale::from(font_pref.pt_size * 4.0 / 3.0));
 
         Self {
             title: <_>::default(),
@@ -94,6 +85,8 @@ impl AbGlyphTitleText {
         let mut pixmap = Pixmap::new(width, height)?;
 
         let pixels = pixmap.pixels_mut();
+        // Glyph coverage per pixel, the pixels only hold it scaled by the color alpha.
+        let mut coverage = vec![0.0; pixels.len()];
 
         for glyph in glyphs {
             if let Some(outline) = self.font.outline_glyph(glyph) {
@@ -102,16 +95,10 @@ impl AbGlyphTitleText {
                 let top = bounds.min.y as u32;
                 outline.draw(|x, y, c| {
                     let p_idx = (top + y) * width + (left + x);
-                    let old_alpha_u8 = pixels[p_idx as usize].alpha();
-                    let new_alpha = c + (old_alpha_u8 as f32 / 255.0);
-                    if let Some(px) = PremultipliedColorU8::from_rgba(
-                        (self.color.red() * new_alpha * 255.0) as _,
-                        (self.color.green() * new_alpha * 255.0) as _,
-                        (self.color.blue() * new_alpha * 255.0) as _,
-                        (new_alpha * 255.0) as _,
-                    ) {
-                        pixels[p_idx as usize] = px;
-                    }
+                    let old_coverage = coverage[p_idx as usize];
+                    let new_coverage = (c + old_coverage).min(1.0);
+                    coverage[p_idx as usize] = new_coverage;
+                    pixels[p_idx as usize] = premultiplied(self.color, new_coverage);
                 })
             }
         }
@@ -145,6 +132,62 @@ impl AbGlyphTitleText {
     }
 }
 
+/// The font color with its alpha scaled by the glyph coverage, premultiplied.
+fn premultiplied(color: Color, coverage: f32) -> PremultipliedColorU8 {
+    let mut color = color;
+    color.apply_opacity(coverage);
+    color.premultiply().to_color_u8()
+}
+
+/// Loads the font matching `pref`, fonts are loaded once per thread and shared by all titles.
+fn cached_font(pref: &FontPreference) -> FontArc {
+    thread_local! {
+        static FONTS: RefCell<Vec<(String, Option<String>, FontArc)>> = RefCell::default();
+    }
+
+    FONTS.with(|fonts| {
+        let mut fonts = fonts.borrow_mut();
+        let cached = fonts
+            .iter()
+            .find(|(name, style, _)| *name == pref.name && *style == pref.style);
+        if let Some((_, _, font)) = cached {
+            return font.clone();
+        }
+
+        let font = load_font(pref);
+        fonts.push((pref.name.clone(), pref.style.clone(), font.clone()));
+        font
+    })
+}
+
+fn load_font(pref: &FontPreference) -> FontArc {
+    font_file_matching(pref)
+        .and_then(read_to_vec)
+        .and_then(|data| {
+            // Without units per em the font can't be scaled to a point size.
+            let mut font = FontVec::try_from_vec(data)
+                .ok()
+                .filter(|font| font.units_per_em().is_some())?;
+            // basic "bold" handling for variable fonts
+            if pref
+                .style
+                .as_ref()
+                .is_some_and(|s| s.eq_ignore_ascii_case("bold"))
+            {
+                font.set_variation(b"wght", 700.0);
+            }
+            Some(FontArc::from(font))
+        })
+        // fallback to using embedded font if system font doesn't work
+        .unwrap_or_else(|| {
+            debug!(
+                "Font {:?} not found, using the embedded Cantarell",
+                pref.name
+            );
+            FontArc::try_from_slice(CANTARELL).expect("embedded Cantarell is a valid font")
+        })
+}
+
 /// Font-config without dynamically linked dependencies
 fn font_file_matching(pref: &FontPreference) -> Option<File> {
     let mut pattern = pref.name.clone();
@@ -1,20 +1,28 @@
 //! System font configuration.
-use crate::title::font_preference::FontPreference;
-use std::process::Command;
+use crate::{
+    config,
+    logging::{debug, warn},
+    title::font_preference::FontPreference,
+};
 
 /// Query system for which font to use for window titles.
 pub(crate) fn titlebar_font() -> Option<FontPreference> {
-    // outputs something like: `'Cantarell Bold 12'`
-    let stdout = Command::new("gsettings")
-        .args(["get", "org.gnome.desktop.wm.preferences", "titlebar-font"])
-        .output()
-        .ok()
-        .and_then(|out| String::from_utf8(out.stdout).ok())?;
+    let font = config::titlebar_font().value?;
+    FontPreference::from_name_style_size(&font)
+}
+
+/// The font described by `font`, falling back to the system titlebar font.
+pub(crate) fn font_preference(font: Option<&str>) -> FontPreference {
+    let configured = font.and_then(FontPreference::from_name_style_size);
+    if let (Some(font), None) = (font, &configured) {
+        warn!(
+            "Invalid title font {:?}, using the system titlebar font",
+            font
+        );
+    }
 
-    FontPreference::from_name_style_size(
-        stdout
-            .trim()
-            .trim_end_matches('\'')
-            .trim_start_matches('\''),
-    )
+    configured.or_else(titlebar_font).unwrap_or_else(|| {
+        debug!("No system titlebar font, using the default font");
+        FontPreference::default()
+    })
 }
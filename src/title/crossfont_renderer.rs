@@ -30,11 +30,11 @@ impl std::fmt::Debug for CrossfontTitleText {
 }
 
 impl CrossfontTitleText {
-    pub fn new(color: Color) -> Result<Self, crossfont::Error> {
+    pub fn new(color: Color, font: Option<&str>) -> Result<Self, crossfont::Error> {
         let title = "".into();
         let scale = 1;
 
-        let font_pref = config::titlebar_font().unwrap_or_default();
+        let font_pref = config::font_preference(font);
         let font_style = font_pref
             .style
             .map(crossfont::Style::Specific)
@@ -167,8 +167,10 @@ impl CrossfontTitleText {
                     (r + g + b) / 3.0
                 };
 
+                // Scale the alpha of the font color by the coverage, so translucent colors
+                // stay translucent.
                 let mut color = self.color;
-                color.set_alpha(alpha);
+                color.apply_opacity(alpha);
                 let color = color.premultiply().to_color_u8();
 
                 buffer.push(color.red());
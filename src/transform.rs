@@ -0,0 +1,142 @@
+//! Pre-transformed buffers for rotated and flipped outputs.
+use smithay_client_toolkit::reexports::client::protocol::wl_output::Transform;
+use tiny_skia::{IntRect, Pixmap};
+
+use crate::SkiaResult;
+
+/// Size of a buffer holding a `width` x `height` surface under `transform`.
+pub fn buffer_size(transform: Transform, width: u32, height: u32) -> (u32, u32) {
+    match transform {
+        Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 => {
+            (height, width)
+        }
+        _ => (width, height),
+    }
+}
+
+/// Surface coordinates covering `damage`, given in buffer pixels of a surface with the logical
+/// `size` and the buffer `scale`.
+///
+/// Transformed buffers are always damaged as a whole, so their whole surface is returned.
+pub fn surface_damage(
+    damage: IntRect,
+    scale: u32,
+    transform: Transform,
+    size: (u32, u32),
+) -> Option<IntRect> {
+    if transform != Transform::Normal {
+        return IntRect::from_xywh(0, 0, size.0, size.1);
+    }
+
+    let scale = scale as i32;
+    let left = damage.left().div_euclid(scale).max(0);
+    let top = damage.top().div_euclid(scale).max(0);
+    let right = (damage.right() + scale - 1)
+        .div_euclid(scale)
+        .min(size.0 as i32);
+    let bottom = (damage.bottom() + scale - 1)
+        .div_euclid(scale)
+        .min(size.1 as i32);
+    IntRect::from_ltrb(left, top, right, bottom)
+}
+
+/// Buffer coordinates of the surface pixel `(x, y)`, following the conventions of
+/// `wl_surface.set_buffer_transform`.
+fn buffer_coords(transform: Transform, width: u32, height: u32, x: u32, y: u32) -> (u32, u32) {
+    let (w, h) = (width - 1, height - 1);
+    match transform {
+        Transform::Flipped => (w - x, y),
+        Transform::_90 => (y, w - x),
+        Transform::Flipped90 => (y, x),
+        Transform::_180 => (w - x, h - y),
+        Transform::Flipped180 => (x, h - y),
+        Transform::_270 => (h - y, x),
+        Transform::Flipped270 => (h - y, w - x),
+        _ => (x, y),
+    }
+}
+
+/// Copies `width` x `height` pixels drawn in surface orientation into a buffer of `transform`.
+pub fn copy_transformed(src: &[u8], width: u32, height: u32, transform: Transform, dst: &mut [u8]) {
+    if transform == Transform::Normal {
+        dst.copy_from_slice(src);
+        return;
+    }
+
+    let (buffer_width, _) = buffer_size(transform, width, height);
+    for (id, pixel) in src.chunks_exact(4).enumerate() {
+        let x = id as u32 % width;
+        let y = id as u32 / width;
+        let (bx, by) = buffer_coords(transform, width, height, x, y);
+        let offset = 4 * (by * buffer_width + bx) as usize;
+        dst[offset..offset + 4].copy_from_slice(pixel);
+    }
+}
+
+/// Runs `draw` on a `width` x `height` canvas in surface orientation and stores the result in
+/// the buffer `canvas` of `transform`.
+pub fn draw_transformed<F>(
+    canvas: &mut [u8],
+    width: u32,
+    height: u32,
+    transform: Transform,
+    draw: F,
+) -> SkiaResult
+where
+    F: FnOnce(&mut [u8]) -> SkiaResult,
+{
+    if transform == Transform::Normal {
+        return draw(canvas);
+    }
+
+    let mut pixmap = Pixmap::new(width, height)?;
+    draw(pixmap.data_mut())?;
+    copy_transformed(pixmap.data(), width, height, transform, canvas);
+
+    Some(())
+}
+
+#[test]
+fn rotated_buffer_size() {
+    assert_eq!(buffer_size(Transform::Normal, 3, 2), (3, 2));
+    assert_eq!(buffer_size(Transform::_180, 3, 2), (3, 2));
+    assert_eq!(buffer_size(Transform::_90, 3, 2), (2, 3));
+    assert_eq!(buffer_size(Transform::Flipped270, 3, 2), (2, 3));
+}
+
+#[test]
+fn copy_rotated_90() {
+    // 2x1 surface: a red pixel followed by a green one.
+    let src = [255, 0, 0, 255, 0, 255, 0, 255];
+    let mut dst = [0; 8];
+    copy_transformed(&src, 2, 1, Transform::_90, &mut dst);
+    // The 1x2 buffer holds the last column of the surface on top.
+    assert_eq!(dst, [0, 255, 0, 255, 255, 0, 0, 255]);
+}
+
+#[test]
+fn copy_flipped() {
+    let src = [1, 1, 1, 1, 2, 2, 2, 2];
+    let mut dst = [0; 8];
+    copy_transformed(&src, 2, 1, Transform::Flipped, &mut dst);
+    assert_eq!(dst, [2, 2, 2, 2, 1, 1, 1, 1]);
+}
+
+#[test]
+fn surface_damage_covers_scaled_damage() {
+    let damage = IntRect::from_xywh(3, 4, 5, 2).unwrap();
+    let size = (100, 50);
+    assert_eq!(
+        surface_damage(damage, 1, Transform::Normal, size),
+        Some(damage)
+    );
+    // Partially covered surface pixels are damaged too.
+    assert_eq!(
+        surface_damage(damage, 2, Transform::Normal, size),
+        IntRect::from_ltrb(1, 2, 4, 3)
+    );
+    assert_eq!(
+        surface_damage(damage, 2, Transform::_90, size),
+        IntRect::from_xywh(0, 0, 100, 50)
+    );
+}
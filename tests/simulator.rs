@@ -0,0 +1,156 @@
+//! The pointer handling of the frame, driven by synthetic input.
+//!
+//! Run with `cargo test --features testing`.
+use sctk_adwaita::{
+    core::ResizeEdge,
+    hit_test,
+    testing::{PointerOutput, PointerRequest, PointerSimulator, BUTTON_LEFT, BUTTON_RIGHT},
+    ButtonKind, DecorationEvent, DecorationHit, RenderState,
+};
+
+fn window() -> PointerSimulator {
+    PointerSimulator::new(RenderState::new((400, 300), 1))
+}
+
+/// Center of `kind` in the titlebar.
+fn button(state: &RenderState, kind: ButtonKind) -> (f64, f64) {
+    let y = 30.0;
+    let xs: Vec<_> = (0..state.size.0 + 20)
+        .map(f64::from)
+        .filter(|&x| hit_test(state, x, y) == DecorationHit::Button(kind))
+        .collect();
+    (xs[xs.len() / 2], y)
+}
+
+fn requests(output: &[PointerOutput]) -> Vec<PointerRequest> {
+    output
+        .iter()
+        .filter_map(|output| match output {
+            PointerOutput::Request(request) => Some(*request),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn dragging_the_titlebar_moves_the_window() {
+    let mut pointer = window();
+    pointer.motion(100.0, 30.0);
+    assert!(pointer.press(BUTTON_LEFT, 1000).is_empty());
+    // Within the drag threshold the press may still be a click.
+    assert!(pointer.motion(104.0, 30.0).is_empty());
+    let output = pointer.motion(108.0, 30.0);
+    assert_eq!(
+        output,
+        [
+            PointerOutput::Event(DecorationEvent::DragStarted),
+            PointerOutput::Request(PointerRequest::Move),
+        ]
+    );
+
+    // The pointer is only seen again once the compositor finished the move.
+    let output = pointer.motion(150.0, 30.0);
+    assert_eq!(output, [PointerOutput::Event(DecorationEvent::DragEnded)]);
+}
+
+#[test]
+fn touchpad_taps() {
+    let mut pointer = window();
+    pointer.motion(100.0, 30.0);
+
+    // A tap jittering below the drag threshold is a click, not a move.
+    let mut output = pointer.press(BUTTON_LEFT, 1000);
+    output.extend(pointer.motion(102.0, 31.0));
+    output.extend(pointer.release(BUTTON_LEFT, 1000));
+    assert!(requests(&output).is_empty());
+
+    // Double taps are reported once the tap timeout expired, both in one go.
+    assert_eq!(requests(&pointer.click(1150)), [PointerRequest::Maximize]);
+
+    // Tap and drag moves the window after all.
+    pointer.press(BUTTON_LEFT, 3000);
+    assert_eq!(
+        requests(&pointer.motion(120.0, 30.0)),
+        [PointerRequest::Move]
+    );
+}
+
+#[test]
+fn buttons_act_on_release() {
+    let mut pointer = window();
+    let state = pointer.state.clone();
+    let (x, y) = button(&state, ButtonKind::Close);
+
+    let output = pointer.motion(x, y);
+    assert!(
+        output.contains(&PointerOutput::Event(DecorationEvent::HoverEnter(
+            ButtonKind::Close
+        )))
+    );
+    assert_eq!(pointer.hovered(), Some(ButtonKind::Close));
+
+    assert!(requests(&pointer.press(BUTTON_LEFT, 1000)).is_empty());
+    assert_eq!(
+        requests(&pointer.release(BUTTON_LEFT, 1000)),
+        [PointerRequest::Close]
+    );
+
+    // Released elsewhere, the press is cancelled.
+    let (x, y) = button(&state, ButtonKind::Minimize);
+    pointer.press(BUTTON_LEFT, 2000);
+    pointer.motion(x, y);
+    assert!(requests(&pointer.release(BUTTON_LEFT, 2000)).is_empty());
+}
+
+#[test]
+fn double_click_maximizes() {
+    let mut pointer = window();
+    pointer.motion(100.0, 30.0);
+    pointer.click(1000);
+    assert_eq!(requests(&pointer.click(1100)), [PointerRequest::Maximize]);
+
+    pointer.state.maximized = true;
+    pointer.click(2000);
+    assert_eq!(requests(&pointer.click(2100)), [PointerRequest::UnMaximize]);
+}
+
+#[test]
+fn borders_resize() {
+    let mut pointer = window();
+    // The bottom border, below the content.
+    pointer.motion(200.0, 10.0 + 35.0 + 300.0 + 5.0);
+    let output = pointer.press(BUTTON_LEFT, 1000);
+    assert_eq!(
+        output,
+        [
+            PointerOutput::Event(DecorationEvent::ResizeStarted(ResizeEdge::Bottom)),
+            PointerOutput::Request(PointerRequest::Resize(ResizeEdge::Bottom)),
+        ]
+    );
+
+    pointer.state.resizable = false;
+    pointer.release(BUTTON_LEFT, 1000);
+    assert!(pointer.press(BUTTON_LEFT, 2000).is_empty());
+}
+
+#[test]
+fn window_menu_on_right_click() {
+    let mut pointer = window();
+    pointer.motion(110.0, 30.0);
+    assert_eq!(
+        requests(&pointer.press(BUTTON_RIGHT, 1000)),
+        [PointerRequest::ShowMenu { x: 100, y: -15 }]
+    );
+}
+
+#[test]
+fn content_is_left_to_the_app() {
+    let mut pointer = window();
+    pointer.motion(100.0, 30.0);
+    let output = pointer.motion(100.0, 100.0);
+    // Leaving the header for the content.
+    assert_eq!(output, [PointerOutput::Request(PointerRequest::Refresh)]);
+    assert!(pointer.press(BUTTON_LEFT, 1000).is_empty());
+    assert_eq!(pointer.leave().len(), 1);
+    assert!(pointer.press(BUTTON_LEFT, 2000).is_empty());
+}
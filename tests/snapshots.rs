@@ -0,0 +1,151 @@
+//! Golden image tests of the decoration drawing.
+//!
+//! References live in `tests/snapshots`. Run with `UPDATE_SNAPSHOTS=1` to (re)create them after
+//! an intended visual change, mismatching renders are written next to the test binaries for
+//! inspection. Titles are left out, their font depends on the system configuration.
+use std::path::{Path, PathBuf};
+
+use sctk_adwaita::{render_decorations, theme::ColorTheme, ButtonKind, Edges, RenderState};
+use tiny_skia::Pixmap;
+
+fn assert_snapshot(name: &str, state: RenderState) {
+    let pixmap = render_decorations(&state).expect("failed to render decorations");
+    let png = pixmap.encode_png().expect("failed to encode snapshot");
+
+    let reference = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.png"));
+
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&reference, &png).expect("failed to write snapshot");
+        return;
+    }
+
+    let expected = Pixmap::load_png(&reference).unwrap_or_else(|err| {
+        panic!(
+            "missing snapshot {}, run with UPDATE_SNAPSHOTS=1 to create it: {err}",
+            reference.display()
+        )
+    });
+    // Compare after the same PNG round trip, which demultiplies the colors.
+    let actual = Pixmap::decode_png(&png).expect("failed to decode snapshot");
+
+    if actual != expected {
+        let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.png"));
+        std::fs::write(&path, &png).expect("failed to write mismatching render");
+        panic!(
+            "snapshot {name} differs from {}, the render was written to {}",
+            reference.display(),
+            path.display()
+        );
+    }
+}
+
+fn state(theme: ColorTheme) -> RenderState {
+    RenderState {
+        theme,
+        ..RenderState::new((240, 60), 1)
+    }
+}
+
+#[test]
+fn light_active() {
+    assert_snapshot("light_active", state(ColorTheme::light()));
+}
+
+#[test]
+fn light_inactive() {
+    assert_snapshot(
+        "light_inactive",
+        RenderState {
+            active: false,
+            ..state(ColorTheme::light())
+        },
+    );
+}
+
+#[test]
+fn dark_active() {
+    assert_snapshot("dark_active", state(ColorTheme::dark()));
+}
+
+#[test]
+fn dark_inactive() {
+    assert_snapshot(
+        "dark_inactive",
+        RenderState {
+            active: false,
+            ..state(ColorTheme::dark())
+        },
+    );
+}
+
+#[test]
+fn close_hovered() {
+    assert_snapshot(
+        "close_hovered",
+        RenderState {
+            hovered: Some(ButtonKind::Close),
+            ..state(ColorTheme::light())
+        },
+    );
+}
+
+#[test]
+fn maximize_hovered() {
+    assert_snapshot(
+        "maximize_hovered",
+        RenderState {
+            hovered: Some(ButtonKind::Maximize),
+            ..state(ColorTheme::dark())
+        },
+    );
+}
+
+#[test]
+fn maximized() {
+    assert_snapshot(
+        "maximized",
+        RenderState {
+            maximized: true,
+            ..state(ColorTheme::light())
+        },
+    );
+}
+
+#[test]
+fn tiled() {
+    assert_snapshot(
+        "tiled",
+        RenderState {
+            tiled: Edges {
+                left: true,
+                ..Edges::NONE
+            },
+            ..state(ColorTheme::light())
+        },
+    );
+}
+
+/// Narrower than both rounded corners, with no room for the buttons.
+#[test]
+fn narrow() {
+    assert_snapshot(
+        "narrow",
+        RenderState {
+            theme: ColorTheme::light(),
+            ..RenderState::new((20, 30), 2)
+        },
+    );
+}
+
+#[test]
+fn scale_2() {
+    assert_snapshot(
+        "scale_2",
+        RenderState {
+            scale: 2,
+            ..state(ColorTheme::light())
+        },
+    );
+}